@@ -0,0 +1,251 @@
+//! User configuration
+//!
+//! Loaded from `$XDG_CONFIG_HOME/doubletap-rl/config.toml` (usually
+//! `~/.config/doubletap-rl/config.toml`). Every field has a default, so a
+//! missing file or missing keys fall back to the built-in behaviour.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::game_state::GamePhase;
+use crate::hooks::HookConfig;
+use crate::hotkey::Hotkey;
+use crate::notifications::NotificationConfig;
+use crate::DoubleTapError;
+
+/// Name of the implicit profile made of the top-level settings
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Default window title to match against
+pub const DEFAULT_TARGET_WINDOW: &str = "Rocket League (64-bit, DX11, Cooked)";
+
+/// Delay (ms) before auto-click — the compositor needs a brief window to
+/// process the physical release before our click arrives. 15ms is reliable
+/// and still under 1 game frame (16.6ms at 60fps), so it's imperceptible.
+pub const DEFAULT_DELAY_MS: u64 = 15;
+
+/// Longest delay accepted while running; anything longer would land well
+/// after the double-tap window
+pub const MAX_DELAY_MS: u64 = 500;
+
+/// Top-level configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Window title the focus detector matches on
+    pub target_window: String,
+
+    /// Delay (ms) between the physical release and our auto-click
+    pub delay_ms: u64,
+
+    /// Key chord that pauses/resumes auto-clicking (e.g. "Ctrl+Alt+D")
+    pub toggle_hotkey: Option<Hotkey>,
+
+    /// Register the io.github.doubletap_rl D-Bus service
+    pub dbus_service: bool,
+
+    /// Show a StatusNotifierItem tray icon
+    pub tray: bool,
+
+    /// Append statistics to the history file for `doubletap-rl stats`
+    pub history: bool,
+
+    /// JSON-lines log of every trigger decision (off when unset)
+    pub event_log: Option<PathBuf>,
+
+    /// Profile active at startup
+    pub profile: String,
+
+    /// Named setting overrides, switchable at runtime
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Optional conditions checked in addition to window focus
+    pub gates: GateConfig,
+
+    /// Desktop notifications on state changes
+    pub notifications: NotificationConfig,
+
+    /// Shell commands run on lifecycle and state events
+    pub hooks: HookConfig,
+}
+
+/// Overrides applied on top of the top-level settings
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub delay_ms: Option<u64>,
+    pub game_phases: Option<Vec<GamePhase>>,
+}
+
+/// Optional auto-click gates
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GateConfig {
+    /// Suppress auto-clicks while the cursor is visible (menus)
+    pub cursor: bool,
+
+    /// Suppress auto-clicks while the pointer is outside the focused window
+    /// (unless it is fullscreen)
+    pub pointer: bool,
+
+    /// Only auto-click in these game phases, read from Launch.log
+    /// (empty = any phase, log not tailed)
+    pub game_phases: Vec<GamePhase>,
+
+    /// Launch.log path (searched in the Steam libraries when unset)
+    pub launch_log: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            target_window: DEFAULT_TARGET_WINDOW.to_string(),
+            delay_ms: DEFAULT_DELAY_MS,
+            toggle_hotkey: None,
+            dbus_service: false,
+            tray: false,
+            history: true,
+            event_log: None,
+            profile: DEFAULT_PROFILE.to_string(),
+            profiles: BTreeMap::new(),
+            gates: GateConfig::default(),
+            notifications: NotificationConfig::default(),
+            hooks: HookConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the config from the default location, or defaults if absent
+    pub fn load() -> Result<Self, DoubleTapError> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load_from(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Load the config from a specific file
+    pub fn load_from(path: &Path) -> Result<Self, DoubleTapError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| DoubleTapError::Config(format!("{}: {}", path.display(), e)))?;
+        toml::from_str(&contents)
+            .map_err(|e| DoubleTapError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Names of all selectable profiles, starting with the default one
+    pub fn profile_names(&self) -> Vec<String> {
+        std::iter::once(DEFAULT_PROFILE.to_string())
+            .chain(self.profiles.keys().cloned())
+            .collect()
+    }
+
+    /// `name` if it is still a profile, else the startup profile, else the
+    /// default one; for a reload that dropped the active profile
+    pub fn existing_profile<'a>(&'a self, name: &'a str) -> &'a str {
+        let exists = |name: &str| name == DEFAULT_PROFILE || self.profiles.contains_key(name);
+        if exists(name) {
+            name
+        } else if exists(&self.profile) {
+            &self.profile
+        } else {
+            DEFAULT_PROFILE
+        }
+    }
+
+    /// Whether any profile restricts auto-clicks to certain game phases
+    pub fn uses_game_phases(&self) -> bool {
+        !self.gates.game_phases.is_empty()
+            || self
+                .profiles
+                .values()
+                .any(|profile| profile.game_phases.as_ref().is_some_and(|p| !p.is_empty()))
+    }
+
+    /// Settings with the named profile's overrides applied
+    pub fn with_profile(&self, name: &str) -> Result<Self, DoubleTapError> {
+        let mut config = self.clone();
+        config.profile = name.to_string();
+        if name == DEFAULT_PROFILE {
+            return Ok(config);
+        }
+
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| DoubleTapError::Config(format!("Unknown profile '{}'", name)))?;
+        if let Some(delay_ms) = profile.delay_ms {
+            config.delay_ms = delay_ms;
+        }
+        if let Some(game_phases) = &profile.game_phases {
+            config.gates.game_phases = game_phases.clone();
+        }
+        Ok(config)
+    }
+
+    /// Default config file path
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("doubletap-rl").join("config.toml"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            delay_ms = 20
+            profile = "ranked"
+
+            [gates]
+            game_phases = ["match", "training"]
+
+            [profiles.ranked]
+            delay_ms = 12
+
+            [profiles.training]
+            game_phases = ["training"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn profiles_override_only_what_they_set() {
+        let config = config();
+
+        let ranked = config.with_profile("ranked").unwrap();
+        assert_eq!(ranked.profile, "ranked");
+        assert_eq!(ranked.delay_ms, 12);
+        assert_eq!(ranked.gates.game_phases, [GamePhase::Match, GamePhase::Training]);
+
+        let training = config.with_profile("training").unwrap();
+        assert_eq!(training.delay_ms, 20);
+        assert_eq!(training.gates.game_phases, [GamePhase::Training]);
+
+        let default = config.with_profile(DEFAULT_PROFILE).unwrap();
+        assert_eq!(default.profile, DEFAULT_PROFILE);
+        assert_eq!(default.delay_ms, 20);
+    }
+
+    #[test]
+    fn unknown_profiles_are_an_error() {
+        let error = config().with_profile("casual").unwrap_err();
+        assert_eq!(error.to_string(), "Invalid configuration: Unknown profile 'casual'");
+    }
+
+    #[test]
+    fn a_dropped_profile_falls_back_to_the_startup_one() {
+        let mut config = config();
+        assert_eq!(config.existing_profile("training"), "training");
+        assert_eq!(config.existing_profile("casual"), "ranked");
+
+        config.profiles.remove("ranked");
+        assert_eq!(config.existing_profile("casual"), DEFAULT_PROFILE);
+    }
+}
@@ -0,0 +1,328 @@
+//! Unix-socket control interface
+//!
+//! The daemon listens on `$XDG_RUNTIME_DIR/doubletap-rl.sock`. Clients send one
+//! JSON request per line (e.g. `{"command":"set-delay","delay_ms":12}`) and get
+//! one JSON response per line back.
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::config::MAX_DELAY_MS;
+use crate::state::DaemonState;
+use crate::stats::StatsSnapshot;
+use crate::supervisor::WorkerHealth;
+use crate::DoubleTapError;
+
+/// Socket file name inside the runtime directory
+const SOCKET_NAME: &str = "doubletap-rl.sock";
+
+/// How long a client waits for the daemon to answer
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A control request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Report the daemon's state
+    Status,
+    /// Resume auto-clicking
+    Enable,
+    /// Pause auto-clicking
+    Disable,
+    /// Change the auto-click delay
+    SetDelay { delay_ms: u64 },
+    /// Re-read the config file
+    Reload,
+    /// Report session statistics
+    Stats,
+}
+
+/// Snapshot of the daemon's state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub enabled: bool,
+    pub focused: bool,
+    pub delay_ms: u64,
+    #[serde(default)]
+    pub click_count: u64,
+    #[serde(default)]
+    pub profile: String,
+    /// Supervised workers, for spotting restarts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workers: Vec<WorkerHealth>,
+}
+
+impl Status {
+    pub fn from_state(state: &DaemonState) -> Self {
+        Self {
+            enabled: state.is_enabled(),
+            focused: state.is_focused(),
+            delay_ms: state.delay_ms(),
+            click_count: state.click_count(),
+            profile: state.profile(),
+            workers: state.health().workers(),
+        }
+    }
+}
+
+/// Response to a control request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<StatsSnapshot>,
+}
+
+impl Response {
+    fn ok(state: &DaemonState) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            status: Some(Status::from_state(state)),
+            stats: None,
+        }
+    }
+
+    fn with_stats(state: &DaemonState) -> Self {
+        Self {
+            stats: Some(state.stats().snapshot()),
+            ..Self::ok(state)
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            status: None,
+            stats: None,
+        }
+    }
+}
+
+/// Control socket path (`$XDG_RUNTIME_DIR/doubletap-rl.sock`)
+pub fn socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join(SOCKET_NAME),
+        None => {
+            let uid = unsafe { libc::getuid() };
+            std::env::temp_dir().join(format!("doubletap-rl-{}.sock", uid))
+        }
+    }
+}
+
+/// Apply a request to the daemon state
+pub fn handle_request(request: Request, state: &DaemonState) -> Response {
+    match request {
+        Request::Status => {}
+        Request::Enable => state.set_enabled(true, "control socket"),
+        Request::Disable => state.set_enabled(false, "control socket"),
+        Request::SetDelay { delay_ms } if delay_ms > MAX_DELAY_MS => {
+            return Response::error(format!(
+                "delay_ms must be between 0 and {}, got {}",
+                MAX_DELAY_MS, delay_ms
+            ));
+        }
+        Request::SetDelay { delay_ms } => state.set_delay_ms(delay_ms),
+        Request::Reload => state.request_reload(),
+        Request::Stats => return Response::with_stats(state),
+    }
+    Response::ok(state)
+}
+
+/// Control socket server, removed from disk when dropped
+pub struct ControlServer {
+    path: PathBuf,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl ControlServer {
+    /// Bind the control socket and serve requests in a background thread
+    ///
+    /// Each client gets a thread of its own, so one that keeps its
+    /// connection open can't hold up the others. Client threads aren't
+    /// joined; they end once their client hangs up or goes quiet for
+    /// `CLIENT_TIMEOUT`.
+    pub fn start(
+        path: PathBuf,
+        state: DaemonState,
+        running: Arc<AtomicBool>,
+    ) -> Result<Self, DoubleTapError> {
+        let listener = bind(&path)?;
+        info!("Control socket: {:?}", path);
+
+        let handle = thread::spawn(move || {
+            while running.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let state = state.clone();
+                        thread::spawn(move || {
+                            if let Err(e) = serve_client(stream, &state) {
+                                debug!("Control client error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                    Err(e) => {
+                        warn!("Control socket accept failed: {}", e);
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            path,
+            handle: Some(handle),
+        })
+    }
+
+    /// The serving thread, to join once `running` is cleared
+    pub fn take_thread(&mut self) -> Option<thread::JoinHandle<()>> {
+        self.handle.take()
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Bind the socket, replacing a stale one left by a crashed daemon
+fn bind(path: &Path) -> Result<UnixListener, DoubleTapError> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(DoubleTapError::Control(format!(
+                "Another instance is already listening on {}",
+                path.display()
+            )));
+        }
+        let _ = std::fs::remove_file(path);
+    }
+
+    let listener = UnixListener::bind(path)
+        .map_err(|e| DoubleTapError::Control(format!("{}: {}", path.display(), e)))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| DoubleTapError::Control(e.to_string()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| DoubleTapError::Control(e.to_string()))?;
+    Ok(listener)
+}
+
+/// Answer every request line on one client connection
+fn serve_client(stream: UnixStream, state: &DaemonState) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => handle_request(request, state),
+            Err(e) => Response::error(format!("Invalid request: {}", e)),
+        };
+        let mut encoded = serde_json::to_string(&response)?;
+        encoded.push('\n');
+        writer.write_all(encoded.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Send one request to the running daemon and wait for its response
+pub fn send_request(request: &Request) -> Result<Response, DoubleTapError> {
+    let path = socket_path();
+    let map_err = |e: std::io::Error| DoubleTapError::Control(format!("{}: {}", path.display(), e));
+
+    let stream = UnixStream::connect(&path).map_err(|e| {
+        DoubleTapError::Control(format!(
+            "Can't reach daemon at {} ({}). Is doubletap-rl running?",
+            path.display(),
+            e
+        ))
+    })?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT)).map_err(map_err)?;
+
+    let mut encoded = serde_json::to_string(request)
+        .map_err(|e| DoubleTapError::Control(e.to_string()))?;
+    encoded.push('\n');
+    (&stream).write_all(encoded.as_bytes()).map_err(map_err)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).map_err(map_err)?;
+    let response: Response = serde_json::from_str(&line)
+        .map_err(|e| DoubleTapError::Control(format!("Invalid response: {}", e)))?;
+
+    match response.error {
+        Some(error) if !response.ok => Err(DoubleTapError::Control(error)),
+        _ => Ok(response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::focus_detector::FocusState;
+
+    fn state() -> DaemonState {
+        DaemonState::new(Arc::new(FocusState::new()), 15)
+    }
+
+    #[test]
+    fn set_delay_accepts_the_whole_range() {
+        let state = state();
+        for delay_ms in [0, 12, MAX_DELAY_MS] {
+            let response = handle_request(Request::SetDelay { delay_ms }, &state);
+            assert!(response.ok);
+            assert_eq!(response.status.unwrap().delay_ms, delay_ms);
+        }
+    }
+
+    #[test]
+    fn an_idle_client_does_not_block_others() {
+        let path = std::env::temp_dir().join(format!("doubletap-rl-control-{}.sock", std::process::id()));
+        let running = Arc::new(AtomicBool::new(true));
+        let mut server = ControlServer::start(path.clone(), state(), running.clone()).unwrap();
+
+        // Connected, but never sends a request
+        let _idle = UnixStream::connect(&path).unwrap();
+        let busy = UnixStream::connect(&path).unwrap();
+        busy.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        (&busy).write_all(b"{\"command\":\"status\"}\n").unwrap();
+        let mut line = String::new();
+        BufReader::new(&busy).read_line(&mut line).unwrap();
+        let response: Response = serde_json::from_str(&line).unwrap();
+        assert!(response.ok);
+        assert_eq!(response.status.unwrap().delay_ms, 15);
+
+        running.store(false, Ordering::SeqCst);
+        server.take_thread().unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn set_delay_rejects_values_out_of_range() {
+        let state = state();
+        let response = handle_request(Request::SetDelay { delay_ms: 501 }, &state);
+        assert!(!response.ok);
+        assert_eq!(response.error.as_deref(), Some("delay_ms must be between 0 and 500, got 501"));
+        assert_eq!(state.delay_ms(), 15);
+    }
+}
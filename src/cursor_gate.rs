@@ -0,0 +1,139 @@
+//! Cursor visibility gating via the XFixes extension
+//!
+//! Rocket League shows the cursor in menus, where right-click is UI
+//! interaction, and hides it during a match. Watching XFixes cursor-change
+//! notifications lets us suppress auto-clicks whenever a cursor is shown.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tracing::error;
+use x11rb::connection::Connection;
+use x11rb::protocol::xfixes::{ConnectionExt as _, CursorNotifyMask};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+use crate::DoubleTapError;
+
+/// Shared cursor visibility state that can be polled from another thread
+pub struct CursorState {
+    is_visible: AtomicBool,
+}
+
+impl CursorState {
+    pub fn new() -> Self {
+        Self {
+            is_visible: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.is_visible.load(Ordering::SeqCst)
+    }
+
+    fn set_visible(&self, visible: bool) {
+        self.is_visible.store(visible, Ordering::SeqCst);
+    }
+}
+
+impl Default for CursorState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether a cursor image (ARGB pixels) shows anything
+///
+/// Wine hides the cursor by installing a fully transparent one, so a cursor
+/// with no opaque pixels counts as hidden.
+fn is_cursor_visible(pixels: &[u32], width: u16, height: u16) -> bool {
+    if width == 0 || height == 0 {
+        return false;
+    }
+    pixels.iter().any(|pixel| pixel >> 24 != 0)
+}
+
+/// Query the current cursor image and decide whether it is visible
+fn query_cursor_visible(conn: &RustConnection) -> Option<bool> {
+    let image = conn.xfixes_get_cursor_image().ok()?.reply().ok()?;
+    Some(is_cursor_visible(&image.cursor_image, image.width, image.height))
+}
+
+/// Start a background thread that tracks cursor visibility
+///
+/// Subscribes to XFixes DisplayCursorNotify on the root window and re-reads
+/// the cursor image whenever it changes. The thread ends early, after
+/// logging why, if the X connection breaks.
+pub fn start_cursor_watcher(
+    state: Arc<CursorState>,
+    running: Arc<AtomicBool>,
+) -> Result<thread::JoinHandle<()>, DoubleTapError> {
+    let map_err = |e: &dyn std::fmt::Display| {
+        DoubleTapError::FocusDetection(format!("XFixes cursor tracking: {}", e))
+    };
+
+    let (conn, screen_num) = x11rb::connect(None).map_err(|e| map_err(&e))?;
+    let root = conn.setup().roots[screen_num].root;
+
+    conn.xfixes_query_version(5, 0)
+        .map_err(|e| map_err(&e))?
+        .reply()
+        .map_err(|e| map_err(&e))?;
+    conn.xfixes_select_cursor_input(root, CursorNotifyMask::DISPLAY_CURSOR)
+        .map_err(|e| map_err(&e))?;
+    conn.flush().map_err(|e| map_err(&e))?;
+
+    if let Some(visible) = query_cursor_visible(&conn) {
+        state.set_visible(visible);
+    }
+
+    Ok(thread::spawn(move || {
+        while running.load(Ordering::SeqCst) {
+            let mut changed = false;
+            loop {
+                match conn.poll_for_event() {
+                    Ok(Some(Event::XfixesCursorNotify(_))) => changed = true,
+                    Ok(Some(_)) => {}
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!("Cursor watcher lost its X connection: {}", e);
+                        return;
+                    }
+                }
+            }
+
+            if changed {
+                if let Some(visible) = query_cursor_visible(&conn) {
+                    state.set_visible(visible);
+                }
+            }
+
+            thread::sleep(Duration::from_millis(20));
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_empty_cursor_is_hidden() {
+        assert!(!is_cursor_visible(&[], 0, 0));
+        assert!(!is_cursor_visible(&[0xff00_0000], 0, 1));
+    }
+
+    #[test]
+    fn a_fully_transparent_cursor_is_hidden() {
+        // Colour without alpha, as Wine's invisible cursor has
+        assert!(!is_cursor_visible(&[0x0000_0000, 0x00ff_ffff, 0x0012_3456, 0], 2, 2));
+    }
+
+    #[test]
+    fn one_opaque_pixel_makes_the_cursor_visible() {
+        assert!(is_cursor_visible(&[0, 0, 0x0100_0000, 0], 2, 2));
+        assert!(is_cursor_visible(&[0xffff_ffff; 4], 2, 2));
+    }
+}
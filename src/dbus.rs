@@ -0,0 +1,265 @@
+//! D-Bus session service for desktop integration
+//!
+//! Exposes `io.github.doubletap_rl.Control` at `/io/github/doubletap_rl` on the
+//! session bus, so desktop widgets and KDE/GNOME shortcuts can read and drive
+//! the daemon without the control socket.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info};
+use zbus::blocking::connection::Builder;
+use zbus::blocking::object_server::InterfaceRef;
+use zbus::blocking::Connection;
+
+use crate::config::MAX_DELAY_MS;
+use crate::state::DaemonState;
+use crate::DoubleTapError;
+
+/// Well-known bus name
+pub const BUS_NAME: &str = "io.github.doubletap_rl";
+
+/// Object path of the control interface
+pub const OBJECT_PATH: &str = "/io/github/doubletap_rl";
+
+/// How often state is checked for property-change signals
+const SIGNAL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The `io.github.doubletap_rl.Control` interface
+pub struct ControlInterface {
+    state: DaemonState,
+}
+
+#[zbus::interface(name = "io.github.doubletap_rl.Control")]
+impl ControlInterface {
+    /// Whether auto-clicking is enabled
+    #[zbus(property)]
+    fn enabled(&self) -> bool {
+        self.state.is_enabled()
+    }
+
+    /// Whether the game window is focused
+    #[zbus(property)]
+    fn focused(&self) -> bool {
+        self.state.is_focused()
+    }
+
+    /// Auto-clicks sent this session
+    #[zbus(property)]
+    fn click_count(&self) -> u64 {
+        self.state.click_count()
+    }
+
+    /// Auto-click delay in milliseconds
+    #[zbus(property)]
+    fn delay_ms(&self) -> u64 {
+        self.state.delay_ms()
+    }
+
+    #[zbus(property)]
+    fn set_delay_ms(&mut self, delay_ms: u64) -> zbus::fdo::Result<()> {
+        if delay_ms > MAX_DELAY_MS {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "DelayMs must be between 0 and {}, got {}",
+                MAX_DELAY_MS, delay_ms
+            )));
+        }
+        self.state.set_delay_ms(delay_ms);
+        Ok(())
+    }
+
+    /// Flip the enabled flag, returning the new value
+    fn toggle(&self) -> bool {
+        self.state.toggle_enabled("D-Bus")
+    }
+
+    /// Re-read the config file
+    fn reload(&self) {
+        self.state.request_reload();
+    }
+}
+
+/// Property values last announced on the bus
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Snapshot {
+    enabled: bool,
+    focused: bool,
+    click_count: u64,
+    delay_ms: u64,
+}
+
+impl Snapshot {
+    fn from_state(state: &DaemonState) -> Self {
+        Self {
+            enabled: state.is_enabled(),
+            focused: state.is_focused(),
+            click_count: state.click_count(),
+            delay_ms: state.delay_ms(),
+        }
+    }
+}
+
+/// Running D-Bus service; the name is released when dropped
+pub struct DbusService {
+    _connection: Connection,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl DbusService {
+    /// Register the service on the session bus
+    pub fn start(state: DaemonState, running: Arc<AtomicBool>) -> Result<Self, DoubleTapError> {
+        Self::start_with(Builder::session(), state, running)
+    }
+
+    /// Register the service on the bus at `address` (e.g. a private
+    /// `dbus-daemon` used for testing)
+    pub fn start_at_address(
+        address: &str,
+        state: DaemonState,
+        running: Arc<AtomicBool>,
+    ) -> Result<Self, DoubleTapError> {
+        Self::start_with(Builder::address(address), state, running)
+    }
+
+    fn start_with(
+        builder: zbus::Result<Builder<'static>>,
+        state: DaemonState,
+        running: Arc<AtomicBool>,
+    ) -> Result<Self, DoubleTapError> {
+        let interface = ControlInterface {
+            state: state.clone(),
+        };
+        let connection = builder
+            .and_then(|builder| builder.name(BUS_NAME))
+            .and_then(|builder| builder.serve_at(OBJECT_PATH, interface))
+            .and_then(|builder| builder.build())
+            .map_err(|e| DoubleTapError::Dbus(e.to_string()))?;
+        let iface_ref = connection
+            .object_server()
+            .interface::<_, ControlInterface>(OBJECT_PATH)
+            .map_err(|e| DoubleTapError::Dbus(e.to_string()))?;
+        info!("D-Bus service: {} at {}", BUS_NAME, OBJECT_PATH);
+
+        let handle = thread::spawn(move || {
+            let mut last = Snapshot::from_state(&state);
+            while running.load(Ordering::SeqCst) {
+                thread::sleep(SIGNAL_INTERVAL);
+                let current = Snapshot::from_state(&state);
+                if current != last {
+                    if let Err(e) = emit_changes(&iface_ref, last, current) {
+                        debug!("Failed to emit D-Bus property change: {}", e);
+                    }
+                    last = current;
+                }
+            }
+        });
+
+        Ok(Self {
+            _connection: connection,
+            handle: Some(handle),
+        })
+    }
+
+    /// The signal thread, to join once `running` is cleared
+    pub fn take_thread(&mut self) -> Option<thread::JoinHandle<()>> {
+        self.handle.take()
+    }
+}
+
+/// Emit PropertiesChanged for every property that differs
+fn emit_changes(
+    iface_ref: &InterfaceRef<ControlInterface>,
+    last: Snapshot,
+    current: Snapshot,
+) -> zbus::Result<()> {
+    let iface = iface_ref.get();
+    let emitter = iface_ref.signal_emitter();
+
+    if current.enabled != last.enabled {
+        zbus::block_on(iface.enabled_changed(emitter))?;
+    }
+    if current.focused != last.focused {
+        zbus::block_on(iface.focused_changed(emitter))?;
+    }
+    if current.click_count != last.click_count {
+        zbus::block_on(iface.click_count_changed(emitter))?;
+    }
+    if current.delay_ms != last.delay_ms {
+        zbus::block_on(iface.delay_ms_changed(emitter))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::focus_detector::FocusState;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use zbus::blocking::Proxy;
+
+    const INTERFACE: &str = "io.github.doubletap_rl.Control";
+
+    /// A private bus, killed on drop
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        fn start() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--print-address", "--nofork", "--nopidfile"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("dbus-daemon should be on PATH");
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address).unwrap();
+            Self {
+                daemon,
+                address: address.trim().to_string(),
+            }
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon on PATH; run with `cargo test -- --ignored`"]
+    fn serves_the_control_interface() {
+        let bus = Bus::start();
+        let focus = Arc::new(FocusState::new());
+        focus.set_focused(true);
+        let state = DaemonState::new(focus, 15);
+        let running = Arc::new(AtomicBool::new(true));
+        let mut service = DbusService::start_at_address(&bus.address, state.clone(), running.clone()).unwrap();
+
+        let client = Builder::address(bus.address.as_str()).unwrap().build().unwrap();
+        let proxy = Proxy::new(&client, BUS_NAME, OBJECT_PATH, INTERFACE).unwrap();
+        assert!(proxy.get_property::<bool>("Enabled").unwrap());
+        assert!(proxy.get_property::<bool>("Focused").unwrap());
+        assert_eq!(proxy.get_property::<u64>("ClickCount").unwrap(), 0);
+
+        let enabled: bool = proxy.call("Toggle", &()).unwrap();
+        assert!(!enabled);
+        assert!(!state.is_enabled());
+
+        assert!(proxy.set_property("DelayMs", 501u64).is_err());
+        assert_eq!(state.delay_ms(), 15);
+        proxy.set_property("DelayMs", 40u64).unwrap();
+        assert_eq!(state.delay_ms(), 40);
+
+        proxy.call::<_, _, ()>("Reload", &()).unwrap();
+        assert!(state.take_reload_request());
+
+        running.store(false, Ordering::SeqCst);
+        service.take_thread().unwrap().join().unwrap();
+    }
+}
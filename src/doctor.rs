@@ -0,0 +1,386 @@
+//! Setup diagnostics for `doubletap-rl doctor`
+//!
+//! Runs the checks we otherwise go through by hand when it "doesn't work":
+//! the config file, device access, permissions, the display server for each
+//! focus backend, the target window, and, when asked for, a synthetic click
+//! read back from the virtual device's own event node. Each [`Check`] says what it found
+//! and, when it fails, what to do.
+
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::focus_detector::{has_active_window, is_gamescope_session, visible_window_titles};
+use crate::input_listener::echo_trigger;
+use crate::input_simulator::{InputSimulator, DEVICE_NAME};
+use crate::install::{self, UDEV_RULE_PATH};
+
+const UINPUT_PATH: &str = "/dev/uinput";
+
+/// Name of the `--round-trip` check
+const LOOPBACK: &str = "uinput loopback";
+
+/// How long the loopback click may take before it counts as lost
+const ECHO_TIMEOUT: Duration = Duration::from_secs(1);
+
+const UDEV_FIX: &str = "run `sudo doubletap-rl install --udev` (or `sudo usermod -aG input $USER` and log in again)";
+
+/// How a check came out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// Not applicable here, or blocked by an earlier failure
+    Skip,
+}
+
+/// Result of one diagnostic
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    /// What was found
+    pub detail: String,
+    /// What to do about a failure
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Pass,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn skip(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Skip,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.outcome {
+            Outcome::Pass => "PASS",
+            Outcome::Fail => "FAIL",
+            Outcome::Skip => "SKIP",
+        };
+        write!(f, "[{}] {}: {}", label, self.name, self.detail)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n       fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check, in order
+///
+/// The uinput loopback sends a real right-click to whatever window has
+/// focus, so it only runs with `round_trip`.
+pub fn run(round_trip: bool) -> Vec<Check> {
+    let (config_check, config) = check_config();
+    let uinput = check_uinput();
+    let uinput_ok = uinput.outcome == Outcome::Pass;
+    let x11 = check_x11();
+    let x11_ok = x11.outcome == Outcome::Pass;
+
+    vec![
+        config_check,
+        uinput,
+        check_input_devices(),
+        check_permissions(),
+        x11,
+        if x11_ok {
+            check_ewmh()
+        } else {
+            Check::skip("EWMH focus", "no X11 connection")
+        },
+        if x11_ok {
+            check_gamescope()
+        } else {
+            Check::skip("gamescope focus", "no X11 connection")
+        },
+        if x11_ok {
+            check_target_window(&config.target_window)
+        } else {
+            Check::skip("target window", "no X11 connection")
+        },
+        if !round_trip {
+            Check::skip(LOOPBACK, "pass --round-trip to send a right-click through the virtual device")
+        } else if uinput_ok {
+            check_uinput_loopback()
+        } else {
+            Check::skip(LOOPBACK, "no access to /dev/uinput")
+        },
+    ]
+}
+
+/// The config file, falling back to the defaults for later checks
+fn check_config() -> (Check, Config) {
+    const NAME: &str = "config";
+    match Config::load() {
+        Ok(config) => {
+            let detail = match Config::default_path() {
+                Some(path) if path.exists() => format!("loaded {}", path.display()),
+                _ => "no config file, using the defaults".to_string(),
+            };
+            (Check::pass(NAME, detail), config)
+        }
+        Err(e) => (
+            Check::fail(NAME, e.to_string(), "fix the file, or move it away to use the defaults"),
+            Config::default(),
+        ),
+    }
+}
+
+fn check_uinput() -> Check {
+    const NAME: &str = "uinput";
+    if !Path::new(UINPUT_PATH).exists() {
+        return Check::fail(
+            NAME,
+            format!("{} doesn't exist", UINPUT_PATH),
+            "load the module with `sudo modprobe uinput` (and add `uinput` to /etc/modules-load.d/ to keep it)",
+        );
+    }
+    let path = CString::new(UINPUT_PATH).unwrap();
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK) } != 0 {
+        return Check::fail(NAME, format!("{} isn't writable", UINPUT_PATH), UDEV_FIX);
+    }
+    Check::pass(NAME, format!("{} is writable", UINPUT_PATH))
+}
+
+fn check_input_devices() -> Check {
+    const NAME: &str = "input devices";
+    let mice = install::trigger_devices();
+    let readable: Vec<_> = evdev::enumerate()
+        .filter(|(_, device)| {
+            device.name() != Some(DEVICE_NAME)
+                && device
+                    .supported_keys()
+                    .is_some_and(|keys| keys.contains(evdev::Key::BTN_RIGHT))
+        })
+        .map(|(path, device)| format!("{} ({})", path.display(), device.name().unwrap_or("unnamed")))
+        .collect();
+
+    if mice.is_empty() && readable.is_empty() {
+        return Check::fail(NAME, "no mouse found in /dev/input", "plug in a mouse");
+    }
+    if readable.is_empty() {
+        return Check::fail(
+            NAME,
+            format!("none of the {} mice in /dev/input is readable", mice.len()),
+            format!("{}; without one, the event log has no device or kernel timestamp", UDEV_FIX),
+        );
+    }
+    let mut detail = format!("readable: {}", readable.join(", "));
+    if mice.len() > readable.len() {
+        detail.push_str(&format!(" ({} more not readable)", mice.len() - readable.len()));
+    }
+    Check::pass(NAME, detail)
+}
+
+fn check_permissions() -> Check {
+    const NAME: &str = "permissions";
+    let Some(input_gid) = group_id("input") else {
+        return if Path::new(UDEV_RULE_PATH).exists() {
+            Check::pass(NAME, format!("udev rule installed ({})", UDEV_RULE_PATH))
+        } else {
+            Check::fail(NAME, "no `input` group and no udev rule", UDEV_FIX)
+        };
+    };
+    if process_groups().contains(&input_gid) {
+        return Check::pass(NAME, "member of the `input` group");
+    }
+    if Path::new(UDEV_RULE_PATH).exists() {
+        return Check::pass(NAME, format!("udev rule installed ({})", UDEV_RULE_PATH));
+    }
+    if user_listed_in("input") {
+        return Check::fail(
+            NAME,
+            "added to the `input` group, but this session started before that",
+            "log out and back in (or reboot)",
+        );
+    }
+    Check::fail(NAME, "not in the `input` group and no udev rule", UDEV_FIX)
+}
+
+fn check_x11() -> Check {
+    const NAME: &str = "X11";
+    let display = std::env::var("DISPLAY").unwrap_or_default();
+    match x11rb::connect(None) {
+        Ok(_) => Check::pass(NAME, format!("connected to DISPLAY={}", display)),
+        Err(e) => {
+            let fix = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                "focus detection needs XWayland; enable it in your compositor (the game runs on it anyway)"
+            } else {
+                "run from inside your desktop session so DISPLAY is set"
+            };
+            Check::fail(NAME, format!("can't connect to DISPLAY={:?}: {}", display, e), fix)
+        }
+    }
+}
+
+fn check_ewmh() -> Check {
+    const NAME: &str = "EWMH focus";
+    if has_active_window() {
+        Check::pass(NAME, "window manager reports the active window (title and PID backends work)")
+    } else {
+        Check::fail(
+            NAME,
+            "no _NET_ACTIVE_WINDOW on the root window",
+            "use an EWMH-compliant window manager, or focus a window and run again",
+        )
+    }
+}
+
+fn check_gamescope() -> Check {
+    const NAME: &str = "gamescope focus";
+    if is_gamescope_session() {
+        Check::pass(NAME, "running under gamescope, focus is matched by Steam AppID")
+    } else {
+        Check::skip(NAME, "no gamescope atoms on this DISPLAY (only gamescope's own XWayland has them)")
+    }
+}
+
+fn check_target_window(target: &str) -> Check {
+    const NAME: &str = "target window";
+    match visible_window_titles() {
+        Ok(titles) if titles.iter().any(|title| title == target) => {
+            Check::pass(NAME, format!("'{}' is on screen", target))
+        }
+        Ok(_) => Check::fail(
+            NAME,
+            format!("no window titled '{}' is on screen", target),
+            "start the game; if it is running, set `target_window` in config.toml to its exact title",
+        ),
+        Err(e) => Check::fail(
+            NAME,
+            e.to_string(),
+            "use an EWMH-compliant window manager, or the Steam launch wrapper (`doubletap-rl run -- %command%`)",
+        ),
+    }
+}
+
+/// A synthetic right-click read back from the virtual device's own node
+///
+/// This proves uinput accepts our events, not that rdev or the X server
+/// sees them: the click is read from evdev, below both.
+fn check_uinput_loopback() -> Check {
+    const NAME: &str = LOOPBACK;
+    let mut simulator = match InputSimulator::new() {
+        Ok(simulator) => simulator,
+        Err(e) => return Check::fail(NAME, e.to_string(), UDEV_FIX),
+    };
+    let Some(node) = simulator.dev_node().map(Path::to_path_buf) else {
+        return Check::skip(NAME, "virtual device created, but its event node wasn't found");
+    };
+    let echo = echo_trigger(
+        &node,
+        || {
+            if let Err(e) = simulator.send_right_click() {
+                warn!("{}", e);
+            }
+        },
+        ECHO_TIMEOUT,
+    );
+    match echo {
+        Ok(Some(_)) => Check::pass(NAME, "a synthetic right-click came back from the virtual device's event node"),
+        Ok(None) => Check::fail(
+            NAME,
+            format!("a synthetic right-click didn't come back within {:?}", ECHO_TIMEOUT),
+            "check `dmesg` for uinput errors, and that nothing grabs the virtual mouse exclusively",
+        ),
+        Err(e) => Check::skip(NAME, format!("virtual device created, but {}", e)),
+    }
+}
+
+/// GID of the named group, if it exists
+fn group_id(name: &str) -> Option<libc::gid_t> {
+    let name = CString::new(name).ok()?;
+    let group = unsafe { libc::getgrnam(name.as_ptr()) };
+    (!group.is_null()).then(|| unsafe { (*group).gr_gid })
+}
+
+/// Groups this process runs with
+fn process_groups() -> Vec<libc::gid_t> {
+    let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    if count <= 0 {
+        return Vec::new();
+    }
+    let mut groups = vec![0; count as usize];
+    let count = unsafe { libc::getgroups(count, groups.as_mut_ptr()) };
+    groups.truncate(count.max(0) as usize);
+    groups
+}
+
+/// Whether /etc/group lists the current user in the named group
+fn user_listed_in(group: &str) -> bool {
+    let Ok(group) = CString::new(group) else {
+        return false;
+    };
+    unsafe {
+        let user = libc::getpwuid(libc::getuid());
+        let entry = libc::getgrnam(group.as_ptr());
+        if user.is_null() || entry.is_null() {
+            return false;
+        }
+        let user = CStr::from_ptr((*user).pw_name);
+        let mut member = (*entry).gr_mem;
+        while !member.is_null() && !(*member).is_null() {
+            if CStr::from_ptr(*member) == user {
+                return true;
+            }
+            member = member.add(1);
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_passing_check_prints_one_line() {
+        let check = Check::pass("config", "using the defaults");
+        assert_eq!(check.to_string(), "[PASS] config: using the defaults");
+    }
+
+    #[test]
+    fn a_failing_check_prints_its_fix() {
+        let check = Check::fail("uinput", "/dev/uinput is missing", "sudo modprobe uinput");
+        assert_eq!(
+            check.to_string(),
+            "[FAIL] uinput: /dev/uinput is missing\n       fix: sudo modprobe uinput"
+        );
+        assert_eq!(Check::skip("EWMH focus", "no X11 connection").to_string(), "[SKIP] EWMH focus: no X11 connection");
+    }
+
+    #[test]
+    fn the_loopback_only_runs_when_asked_for() {
+        let checks = run(false);
+        let loopback = checks.last().unwrap();
+        assert_eq!(loopback.name, LOOPBACK);
+        assert_eq!(loopback.outcome, Outcome::Skip);
+        assert!(loopback.detail.contains("--round-trip"));
+    }
+}
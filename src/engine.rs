@@ -0,0 +1,505 @@
+//! Deterministic trigger engine
+//!
+//! All auto-click decisions live here as a plain state machine: feed it
+//! [`Input`]s with the time they happened and it returns the [`Action`]s to
+//! perform. It never reads a clock, sleeps or touches a device, so the
+//! whole behaviour — feedback filter, rate limit, focus and gate checks,
+//! the release delay — can be stepped through with a [`VirtualClock`].
+//! [`TriggerPipeline`](crate::pipeline::TriggerPipeline) drives it for the
+//! daemon and for `replay`.
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::event_log::DecisionSnapshot;
+use crate::game_state::GamePhase;
+use crate::stats::SuppressReason;
+
+/// Minimum time between auto-clicks
+/// This prevents feedback loops from our own simulated events
+pub const MIN_CLICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Releases this soon after an auto-click are taken to be its echo; later
+/// ones inside `MIN_CLICK_INTERVAL` are real clicks being rate-limited
+pub const FEEDBACK_WINDOW: Duration = Duration::from_millis(30);
+
+/// Two physical releases this close together count as a natural double-tap
+pub const NATURAL_DOUBLE_TAP_WINDOW: Duration = Duration::from_millis(250);
+
+/// Source of the current time
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Wait for `duration` to pass on this clock
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Clock that only moves when told to; sleeping advances it instantly
+#[derive(Debug)]
+pub struct VirtualClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    /// Time since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// A release of the trigger button
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trigger {
+    /// When the release was seen
+    pub time: Instant,
+    /// Device the release came from (evdev only)
+    pub device: Option<String>,
+    /// Kernel timestamp of the release (evdev only)
+    pub kernel_time: Option<SystemTime>,
+}
+
+/// Something that happened, fed to [`DecisionEngine::handle`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    /// The trigger button was released
+    Trigger(Trigger),
+    /// Target window focus or pointer position changed
+    Focus { focused: bool, pointer_inside: bool },
+    /// The cursor was shown or hidden
+    CursorVisible(bool),
+    /// The game entered another phase
+    GamePhase(GamePhase),
+    /// Auto-clicking was switched on or off
+    SetEnabled(bool),
+    /// The release delay changed
+    SetDelay(Duration),
+    /// The active profile's game-phase gate changed
+    SetGamePhases(Vec<GamePhase>),
+    /// Time passed; fires a scheduled auto-click once it is due
+    Tick,
+    /// The auto-click from [`Action::Click`] was written
+    ClickSent,
+    /// Writing the auto-click from [`Action::Click`] failed
+    ClickFailed,
+}
+
+/// What the engine wants done in response to an input
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// The release followed the previous physical one closely enough to be
+    /// a natural double-tap
+    NaturalDoubleTap,
+    /// The trigger was dropped
+    Suppress {
+        trigger: Trigger,
+        reason: SuppressReason,
+        state: DecisionSnapshot,
+    },
+    /// The trigger passed every check; an auto-click is due at `at`
+    Schedule {
+        trigger: Trigger,
+        at: Instant,
+        delay: Duration,
+        state: DecisionSnapshot,
+    },
+    /// Send the auto-click for `trigger` now, then report back with
+    /// [`Input::ClickSent`] or [`Input::ClickFailed`]
+    Click { trigger: Trigger },
+}
+
+/// The auto-click decision state machine
+#[derive(Debug, Clone)]
+pub struct DecisionEngine {
+    state: DecisionSnapshot,
+    delay: Duration,
+    game_phases: Vec<GamePhase>,
+    last_auto_click: Option<Instant>,
+    last_physical_release: Option<Instant>,
+    pending: Option<(Trigger, Instant)>,
+    in_flight: bool,
+}
+
+impl DecisionEngine {
+    /// Start enabled, unfocused, with the cursor hidden and no phase gate
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            state: DecisionSnapshot {
+                enabled: true,
+                focused: false,
+                pointer_inside: false,
+                cursor_visible: false,
+                game_phase: GamePhase::Unknown,
+            },
+            delay,
+            game_phases: Vec::new(),
+            last_auto_click: None,
+            last_physical_release: None,
+            pending: None,
+            in_flight: false,
+        }
+    }
+
+    /// The conditions triggers are currently checked against
+    pub fn state(&self) -> DecisionSnapshot {
+        self.state
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// When the next [`Input::Tick`] has something to do
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|(_, at)| *at)
+    }
+
+    /// Apply one input that happened at `now`
+    pub fn handle(&mut self, now: Instant, input: Input) -> Vec<Action> {
+        let mut actions = Vec::new();
+        match input {
+            Input::Trigger(trigger) => self.on_trigger(now, trigger, &mut actions),
+            Input::Focus {
+                focused,
+                pointer_inside,
+            } => {
+                self.state.focused = focused;
+                self.state.pointer_inside = pointer_inside;
+            }
+            Input::CursorVisible(visible) => self.state.cursor_visible = visible,
+            Input::GamePhase(phase) => self.state.game_phase = phase,
+            Input::SetEnabled(enabled) => self.state.enabled = enabled,
+            Input::SetDelay(delay) => self.delay = delay,
+            Input::SetGamePhases(phases) => self.game_phases = phases,
+            Input::Tick => {
+                if self.pending.as_ref().is_some_and(|(_, at)| now >= *at) {
+                    let (trigger, _) = self.pending.take().unwrap();
+                    self.in_flight = true;
+                    actions.push(Action::Click { trigger });
+                }
+            }
+            Input::ClickSent => {
+                self.in_flight = false;
+                self.last_auto_click = Some(now);
+            }
+            Input::ClickFailed => self.in_flight = false,
+        }
+        actions
+    }
+
+    fn on_trigger(&mut self, now: Instant, trigger: Trigger, actions: &mut Vec<Action>) {
+        // Check if this might be our own auto-click event
+        let filtered = self.feedback_filter(now);
+
+        // Anything but our own echo is a physical release
+        if filtered != Some(SuppressReason::Feedback) {
+            if self
+                .last_physical_release
+                .is_some_and(|last| now.saturating_duration_since(last) < NATURAL_DOUBLE_TAP_WINDOW)
+            {
+                actions.push(Action::NaturalDoubleTap);
+            }
+            self.last_physical_release = Some(now);
+        }
+
+        let state = self.state;
+        let reason = filtered.or(if !state.enabled {
+            Some(SuppressReason::Disabled)
+        } else if !state.focused || !state.pointer_inside {
+            Some(SuppressReason::Focus)
+        } else if state.cursor_visible || !state.game_phase.is_allowed_by(&self.game_phases) {
+            Some(SuppressReason::Gate)
+        } else {
+            None
+        });
+
+        match reason {
+            Some(reason) => actions.push(Action::Suppress {
+                trigger,
+                reason,
+                state,
+            }),
+            None => {
+                // Brief delay for compositor to process physical release
+                let at = now + self.delay;
+                self.pending = Some((trigger.clone(), at));
+                actions.push(Action::Schedule {
+                    trigger,
+                    at,
+                    delay: self.delay,
+                    state,
+                });
+            }
+        }
+    }
+
+    /// Why a release at `now` is too soon after our own auto-click
+    fn feedback_filter(&self, now: Instant) -> Option<SuppressReason> {
+        // An auto-click already on its way covers this release too
+        if self.pending.is_some() || self.in_flight {
+            return Some(SuppressReason::RateLimit);
+        }
+        let elapsed = now.saturating_duration_since(self.last_auto_click?);
+        if elapsed < FEEDBACK_WINDOW {
+            Some(SuppressReason::Feedback)
+        } else if elapsed < MIN_CLICK_INTERVAL {
+            Some(SuppressReason::RateLimit)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELAY: Duration = Duration::from_millis(15);
+
+    /// Engine with the target focused, so triggers pass every check
+    fn ready_engine() -> DecisionEngine {
+        let mut engine = DecisionEngine::new(DELAY);
+        engine.handle(
+            Instant::now(),
+            Input::Focus {
+                focused: true,
+                pointer_inside: true,
+            },
+        );
+        engine
+    }
+
+    fn trigger(clock: &VirtualClock) -> Input {
+        Input::Trigger(Trigger {
+            time: clock.now(),
+            device: None,
+            kernel_time: None,
+        })
+    }
+
+    fn suppress_reason(actions: &[Action]) -> Option<SuppressReason> {
+        actions.iter().find_map(|action| match action {
+            Action::Suppress { reason, .. } => Some(*reason),
+            _ => None,
+        })
+    }
+
+    fn is_scheduled(actions: &[Action]) -> bool {
+        actions
+            .iter()
+            .any(|action| matches!(action, Action::Schedule { .. }))
+    }
+
+    /// Trigger, wait out the delay and report the click as sent
+    fn click(engine: &mut DecisionEngine, clock: &VirtualClock) {
+        assert!(is_scheduled(&engine.handle(clock.now(), trigger(clock))));
+        clock.advance(DELAY);
+        let actions = engine.handle(clock.now(), Input::Tick);
+        assert!(matches!(actions.as_slice(), [Action::Click { .. }]));
+        engine.handle(clock.now(), Input::ClickSent);
+    }
+
+    #[test]
+    fn schedules_the_click_after_the_delay() {
+        let clock = VirtualClock::new();
+        let mut engine = ready_engine();
+        let now = clock.now();
+
+        let actions = engine.handle(now, trigger(&clock));
+        match actions.as_slice() {
+            [Action::Schedule { at, delay, .. }] => {
+                assert_eq!(*at, now + DELAY);
+                assert_eq!(*delay, DELAY);
+            }
+            other => panic!("expected a schedule, got {:?}", other),
+        }
+        assert_eq!(engine.next_deadline(), Some(now + DELAY));
+    }
+
+    #[test]
+    fn tick_before_the_deadline_does_nothing() {
+        let clock = VirtualClock::new();
+        let mut engine = ready_engine();
+        engine.handle(clock.now(), trigger(&clock));
+
+        clock.advance(DELAY - Duration::from_millis(1));
+        assert!(engine.handle(clock.now(), Input::Tick).is_empty());
+        assert!(engine.next_deadline().is_some());
+
+        clock.advance(Duration::from_millis(1));
+        let actions = engine.handle(clock.now(), Input::Tick);
+        assert!(matches!(actions.as_slice(), [Action::Click { .. }]));
+        assert_eq!(engine.next_deadline(), None);
+    }
+
+    #[test]
+    fn feedback_window_then_rate_limit() {
+        let clock = VirtualClock::new();
+        let mut engine = ready_engine();
+        click(&mut engine, &clock);
+
+        // Echo of our own click
+        clock.advance(FEEDBACK_WINDOW - Duration::from_millis(1));
+        let actions = engine.handle(clock.now(), trigger(&clock));
+        assert_eq!(suppress_reason(&actions), Some(SuppressReason::Feedback));
+
+        // A real click, but too soon
+        clock.advance(Duration::from_millis(1));
+        let actions = engine.handle(clock.now(), trigger(&clock));
+        assert_eq!(suppress_reason(&actions), Some(SuppressReason::RateLimit));
+
+        clock.advance(MIN_CLICK_INTERVAL - FEEDBACK_WINDOW);
+        assert!(is_scheduled(&engine.handle(clock.now(), trigger(&clock))));
+    }
+
+    #[test]
+    fn pending_click_rate_limits_further_triggers() {
+        let clock = VirtualClock::new();
+        let mut engine = ready_engine();
+        engine.handle(clock.now(), trigger(&clock));
+
+        clock.advance(Duration::from_millis(5));
+        let actions = engine.handle(clock.now(), trigger(&clock));
+        assert_eq!(suppress_reason(&actions), Some(SuppressReason::RateLimit));
+    }
+
+    #[test]
+    fn natural_double_tap_inside_the_window() {
+        let clock = VirtualClock::new();
+        let mut engine = DecisionEngine::new(DELAY);
+
+        assert!(!engine
+            .handle(clock.now(), trigger(&clock))
+            .contains(&Action::NaturalDoubleTap));
+        clock.advance(NATURAL_DOUBLE_TAP_WINDOW - Duration::from_millis(1));
+        assert!(engine
+            .handle(clock.now(), trigger(&clock))
+            .contains(&Action::NaturalDoubleTap));
+        clock.advance(NATURAL_DOUBLE_TAP_WINDOW);
+        assert!(!engine
+            .handle(clock.now(), trigger(&clock))
+            .contains(&Action::NaturalDoubleTap));
+    }
+
+    #[test]
+    fn feedback_is_not_a_natural_double_tap() {
+        let clock = VirtualClock::new();
+        let mut engine = ready_engine();
+        click(&mut engine, &clock);
+
+        clock.advance(Duration::from_millis(5));
+        let actions = engine.handle(clock.now(), trigger(&clock));
+        assert_eq!(suppress_reason(&actions), Some(SuppressReason::Feedback));
+        assert!(!actions.contains(&Action::NaturalDoubleTap));
+    }
+
+    #[test]
+    fn suppression_order_is_disabled_focus_gate() {
+        let clock = VirtualClock::new();
+        let mut engine = DecisionEngine::new(DELAY);
+        engine.handle(clock.now(), Input::SetEnabled(false));
+        engine.handle(clock.now(), Input::CursorVisible(true));
+
+        // Everything fails: disabled wins
+        let actions = engine.handle(clock.now(), trigger(&clock));
+        assert_eq!(suppress_reason(&actions), Some(SuppressReason::Disabled));
+
+        clock.advance(Duration::from_secs(1));
+        engine.handle(clock.now(), Input::SetEnabled(true));
+        let actions = engine.handle(clock.now(), trigger(&clock));
+        assert_eq!(suppress_reason(&actions), Some(SuppressReason::Focus));
+
+        // Focused but the pointer is elsewhere
+        clock.advance(Duration::from_secs(1));
+        engine.handle(
+            clock.now(),
+            Input::Focus {
+                focused: true,
+                pointer_inside: false,
+            },
+        );
+        let actions = engine.handle(clock.now(), trigger(&clock));
+        assert_eq!(suppress_reason(&actions), Some(SuppressReason::Focus));
+
+        clock.advance(Duration::from_secs(1));
+        engine.handle(
+            clock.now(),
+            Input::Focus {
+                focused: true,
+                pointer_inside: true,
+            },
+        );
+        let actions = engine.handle(clock.now(), trigger(&clock));
+        assert_eq!(suppress_reason(&actions), Some(SuppressReason::Gate));
+
+        clock.advance(Duration::from_secs(1));
+        engine.handle(clock.now(), Input::CursorVisible(false));
+        engine.handle(clock.now(), Input::SetGamePhases(vec![GamePhase::Match]));
+        engine.handle(clock.now(), Input::GamePhase(GamePhase::Menu));
+        let actions = engine.handle(clock.now(), trigger(&clock));
+        assert_eq!(suppress_reason(&actions), Some(SuppressReason::Gate));
+
+        clock.advance(Duration::from_secs(1));
+        engine.handle(clock.now(), Input::GamePhase(GamePhase::Match));
+        assert!(is_scheduled(&engine.handle(clock.now(), trigger(&clock))));
+    }
+
+    #[test]
+    fn click_failed_clears_in_flight_without_rate_limiting() {
+        let clock = VirtualClock::new();
+        let mut engine = ready_engine();
+        engine.handle(clock.now(), trigger(&clock));
+        clock.advance(DELAY);
+        engine.handle(clock.now(), Input::Tick);
+
+        // Still in flight
+        let actions = engine.handle(clock.now(), trigger(&clock));
+        assert_eq!(suppress_reason(&actions), Some(SuppressReason::RateLimit));
+
+        engine.handle(clock.now(), Input::ClickFailed);
+        clock.advance(Duration::from_millis(1));
+        assert!(is_scheduled(&engine.handle(clock.now(), trigger(&clock))));
+    }
+}
@@ -0,0 +1,351 @@
+//! Recording and replaying raw device streams in evemu format
+//!
+//! `doubletap-rl record` writes a mouse's raw event stream the way
+//! `evemu-record` does, so recordings also work with `evemu-play`.
+//! `doubletap-rl replay` reads one back and feeds its trigger releases
+//! through the decision pipeline with the original timing.
+
+use evdev::raw_stream::RawDevice;
+use evdev::{EventType, Key};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::info;
+
+use crate::input_simulator::DEVICE_NAME;
+use crate::DoubleTapError;
+
+const EV_KEY: u16 = 0x01;
+const BTN_RIGHT: u16 = 0x111;
+
+/// Bitmask sizes (bytes) per event type, as in the kernel headers
+const EV_BYTES: usize = 4;
+const KEY_BYTES: usize = 96;
+const REL_BYTES: usize = 2;
+const ABS_BYTES: usize = 8;
+const MSC_BYTES: usize = 1;
+
+/// One event read back from a recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedEvent {
+    /// Time since the first event of the recording
+    pub time: Duration,
+    pub event_type: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl RecordedEvent {
+    /// Whether this is a release of the trigger button
+    pub fn is_trigger_release(&self) -> bool {
+        self.event_type == EV_KEY && self.code == BTN_RIGHT && self.value == 0
+    }
+}
+
+/// A recording loaded from an evemu file
+#[derive(Debug, Clone)]
+pub struct Recording {
+    /// Name of the recorded device
+    pub name: String,
+    pub events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    /// Parse an evemu recording, keeping the device name and `E:` lines
+    pub fn load(path: &Path) -> Result<Self, DoubleTapError> {
+        let map_err = |e: std::io::Error| DoubleTapError::Recording(format!("{}: {}", path.display(), e));
+        let file = File::open(path).map_err(map_err)?;
+
+        let mut name = String::from("unnamed");
+        let mut events = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(map_err)?;
+            // Everything after '#' is a comment
+            let line = line.split('#').next().unwrap_or_default().trim();
+
+            if let Some(device_name) = line.strip_prefix("N:") {
+                name = device_name.trim().to_string();
+            } else if let Some(event) = line.strip_prefix("E:") {
+                let event = parse_event(event).ok_or_else(|| {
+                    DoubleTapError::Recording(format!(
+                        "{}:{}: malformed event line",
+                        path.display(),
+                        number + 1
+                    ))
+                })?;
+                events.push(event);
+            }
+        }
+
+        // Older evemu versions write absolute times; make them relative
+        if let Some(first) = events.first().map(|event| event.time) {
+            for event in events.iter_mut() {
+                event.time = event.time.saturating_sub(first);
+            }
+        }
+        Ok(Self { name, events })
+    }
+
+    /// Number of trigger releases in the recording
+    pub fn trigger_count(&self) -> usize {
+        self.events.iter().filter(|event| event.is_trigger_release()).count()
+    }
+}
+
+/// Parse the `<sec>.<usec> <type> <code> <value>` part of an `E:` line
+fn parse_event(line: &str) -> Option<RecordedEvent> {
+    let mut fields = line.split_whitespace();
+    let (secs, micros) = fields.next()?.split_once('.')?;
+    let event_type = u16::from_str_radix(fields.next()?, 16).ok()?;
+    let code = u16::from_str_radix(fields.next()?, 16).ok()?;
+    let value = fields.next()?.parse().ok()?;
+    Some(RecordedEvent {
+        time: Duration::from_secs(secs.parse().ok()?)
+            + Duration::from_micros(micros.parse().ok()?),
+        event_type,
+        code,
+        value,
+    })
+}
+
+/// Mice in `/dev/input` we can read, for choosing what to record; our own
+/// virtual device is left out
+pub fn recordable_devices() -> Vec<(PathBuf, String)> {
+    evdev::enumerate()
+        .filter(|(_, device)| {
+            device.name() != Some(DEVICE_NAME)
+                && device
+                    .supported_keys()
+                    .is_some_and(|keys| keys.contains(Key::BTN_RIGHT))
+        })
+        .map(|(path, device)| (path, device.name().unwrap_or("unnamed").to_string()))
+        .collect()
+}
+
+/// Record a device's raw event stream to `output` until the process is
+/// interrupted; the file is flushed after every `SYN_REPORT`
+pub fn record(device_path: &Path, output: &Path) -> Result<(), DoubleTapError> {
+    let mut device = RawDevice::open(device_path)
+        .map_err(|e| DoubleTapError::Recording(format!("{}: {}", device_path.display(), e)))?;
+    let map_err = |e: std::io::Error| DoubleTapError::Recording(format!("{}: {}", output.display(), e));
+    let mut writer = BufWriter::new(File::create(output).map_err(map_err)?);
+    write_header(&mut writer, &device).map_err(map_err)?;
+    writer.flush().map_err(map_err)?;
+
+    info!(
+        "Recording {} ({}) to {:?}, press Ctrl+C to stop",
+        device_path.display(),
+        device.name().unwrap_or("unnamed"),
+        output
+    );
+
+    let mut start: Option<SystemTime> = None;
+    loop {
+        let events = device
+            .fetch_events()
+            .map_err(|e| DoubleTapError::Recording(format!("{}: {}", device_path.display(), e)))?;
+        for event in events {
+            let start = *start.get_or_insert(event.timestamp());
+            let event = RecordedEvent {
+                time: event.timestamp().duration_since(start).unwrap_or_default(),
+                event_type: event.event_type().0,
+                code: event.code(),
+                value: event.value(),
+            };
+            write_event(&mut writer, &event).map_err(map_err)?;
+            if event.event_type == EventType::SYNCHRONIZATION.0 {
+                writer.flush().map_err(map_err)?;
+            }
+        }
+    }
+}
+
+/// Write one `E:` line
+fn write_event(writer: &mut impl Write, event: &RecordedEvent) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "E: {}.{:06} {:04x} {:04x} {:04}",
+        event.time.as_secs(),
+        event.time.subsec_micros(),
+        event.event_type,
+        event.code,
+        event.value
+    )
+}
+
+/// Write the evemu device description
+fn write_header(writer: &mut impl Write, device: &RawDevice) -> std::io::Result<()> {
+    let name = device.name().unwrap_or("unnamed");
+    let id = device.input_id();
+
+    writeln!(writer, "# EVEMU 1.3")?;
+    writeln!(writer, "# Input device name: \"{}\"", name)?;
+    writeln!(
+        writer,
+        "# Input device ID: bus {:#04x} vendor {:#06x} product {:#06x} version {:#06x}",
+        id.bus_type().0,
+        id.vendor(),
+        id.product(),
+        id.version()
+    )?;
+    writeln!(writer, "# Recorded by doubletap-rl {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(writer, "N: {}", name)?;
+    writeln!(
+        writer,
+        "I: {:04x} {:04x} {:04x} {:04x}",
+        id.bus_type().0,
+        id.vendor(),
+        id.product(),
+        id.version()
+    )?;
+    write_bytes(writer, "P:", &bitmask(device.properties().iter().map(|p| p.0), 8))?;
+
+    let types = bitmask(device.supported_events().iter().map(|t| t.0), EV_BYTES);
+    let keys = device
+        .supported_keys()
+        .map(|keys| bitmask(keys.iter().map(|key| key.code()), KEY_BYTES))
+        .unwrap_or_else(|| vec![0; KEY_BYTES]);
+    let rel = device
+        .supported_relative_axes()
+        .map(|axes| bitmask(axes.iter().map(|axis| axis.0), REL_BYTES))
+        .unwrap_or_else(|| vec![0; REL_BYTES]);
+    let abs = device
+        .supported_absolute_axes()
+        .map(|axes| bitmask(axes.iter().map(|axis| axis.0), ABS_BYTES))
+        .unwrap_or_else(|| vec![0; ABS_BYTES]);
+    let msc = device
+        .misc_properties()
+        .map(|misc| bitmask(misc.iter().map(|m| m.0), MSC_BYTES))
+        .unwrap_or_else(|| vec![0; MSC_BYTES]);
+
+    for (event_type, mask) in [(0x00, types), (0x01, keys), (0x02, rel), (0x03, abs), (0x04, msc)] {
+        write_bytes(writer, &format!("B: {:02x}", event_type), &mask)?;
+    }
+
+    if let Some(axes) = device.supported_absolute_axes() {
+        let info = device.get_abs_state()?;
+        for axis in axes.iter() {
+            let abs = &info[axis.0 as usize];
+            writeln!(
+                writer,
+                "A: {:02x} {} {} {} {} {}",
+                axis.0, abs.minimum, abs.maximum, abs.fuzz, abs.flat, abs.resolution
+            )?;
+        }
+    }
+
+    writeln!(writer, "################################")?;
+    writeln!(writer, "#      Waiting for events      #")?;
+    writeln!(writer, "################################")
+}
+
+/// Bitmask of `codes` in the kernel's byte order
+fn bitmask(codes: impl Iterator<Item = u16>, bytes: usize) -> Vec<u8> {
+    let mut mask = vec![0u8; bytes];
+    for code in codes {
+        if let Some(byte) = mask.get_mut(code as usize / 8) {
+            *byte |= 1 << (code % 8);
+        }
+    }
+    mask
+}
+
+/// Write bytes as hex, eight per line, each line starting with `prefix`
+fn write_bytes(writer: &mut impl Write, prefix: &str, bytes: &[u8]) -> std::io::Result<()> {
+    for chunk in bytes.chunks(8) {
+        write!(writer, "{}", prefix)?;
+        for byte in chunk {
+            write!(writer, " {:02x}", byte)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Write `contents` to a scratch file and load it
+    fn load(name: &str, contents: &str) -> Result<Recording, DoubleTapError> {
+        let path = std::env::temp_dir().join(format!("doubletap-rl-test-{}-{}.evemu", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        let recording = Recording::load(&path);
+        let _ = fs::remove_file(&path);
+        recording
+    }
+
+    #[test]
+    fn written_events_parse_back() {
+        let events = [
+            RecordedEvent {
+                time: Duration::ZERO,
+                event_type: EV_KEY,
+                code: BTN_RIGHT,
+                value: 1,
+            },
+            RecordedEvent {
+                time: Duration::from_micros(80_500),
+                event_type: EV_KEY,
+                code: BTN_RIGHT,
+                value: 0,
+            },
+            RecordedEvent {
+                time: Duration::from_micros(1_200_001),
+                event_type: 0x02,
+                code: 0x00,
+                value: -12,
+            },
+        ];
+        for event in events {
+            let mut line = Vec::new();
+            write_event(&mut line, &event).unwrap();
+            let line = String::from_utf8(line).unwrap();
+            let parsed = parse_event(line.trim_end().strip_prefix("E:").unwrap());
+            assert_eq!(parsed, Some(event), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn loads_names_comments_and_relative_times() {
+        let recording = load(
+            "valid",
+            "# EVEMU 1.3\n\
+             N: Test Mouse\n\
+             I: 0003 046d c08b 0111\n\
+             E: 100.000000 0001 0111 0001\n\
+             E: 100.000000 0000 0000 0000\n\
+             E: 100.080000 0001 0111 0000\t# BTN_RIGHT release\n\
+             E: 100.080000 0000 0000 0000\n",
+        )
+        .unwrap();
+
+        assert_eq!(recording.name, "Test Mouse");
+        assert_eq!(recording.events.len(), 4);
+        assert_eq!(recording.events[0].time, Duration::ZERO);
+        assert_eq!(recording.events[2].time, Duration::from_millis(80));
+        assert!(recording.events[2].is_trigger_release());
+        assert_eq!(recording.trigger_count(), 1);
+    }
+
+    #[test]
+    fn malformed_event_lines_are_reported_with_their_line_number() {
+        for (name, line) in [
+            ("no-fraction", "E: 100 0001 0111 0000"),
+            ("bad-type", "E: 100.000000 zz 0111 0000"),
+            ("bad-value", "E: 100.000000 0001 0111 up"),
+            ("missing-value", "E: 100.000000 0001 0111"),
+            ("bad-time", "E: x.000000 0001 0111 0000"),
+        ] {
+            let error = load(name, &format!("N: Test Mouse\nE: 0.000000 0000 0000 0000\n{}\n", line)).unwrap_err();
+            assert!(error.to_string().ends_with(":3: malformed event line"), "{}: {}", line, error);
+        }
+    }
+
+    #[test]
+    fn missing_file_is_an_error() {
+        assert!(Recording::load(Path::new("/nonexistent/recording.evemu")).is_err());
+    }
+}
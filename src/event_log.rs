@@ -0,0 +1,98 @@
+//! JSON-lines log of every trigger decision
+//!
+//! Enabled with `event_log = "<path>"` in the config. Each trigger release
+//! appends one record: where it came from, the state the decision was based
+//! on, what was decided and, for auto-clicks, when the click went out and
+//! whether the write failed. Records are written after the click is sent, so
+//! logging never delays it.
+
+use chrono::{DateTime, Local, SecondsFormat};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::game_state::GamePhase;
+use crate::stats::SuppressReason;
+use crate::DoubleTapError;
+
+/// State a trigger decision was based on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DecisionSnapshot {
+    pub enabled: bool,
+    pub focused: bool,
+    pub pointer_inside: bool,
+    pub cursor_visible: bool,
+    pub game_phase: GamePhase,
+}
+
+/// What was done with a trigger
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Fired,
+    Suppressed,
+}
+
+/// One line of the event log
+#[derive(Debug, Clone, Serialize)]
+pub struct TriggerRecord {
+    /// Wall-clock time the listener saw the release
+    pub time: String,
+    /// Input device the release came from
+    pub device: Option<String>,
+    /// Kernel timestamp of the release event
+    pub kernel_time: Option<String>,
+    pub state: DecisionSnapshot,
+    pub decision: Decision,
+    pub reason: Option<SuppressReason>,
+    /// Delay slept before the auto-click
+    pub delay_ms: Option<u64>,
+    /// Wall-clock time the auto-click was written
+    pub emit_time: Option<String>,
+    /// Time from the release being seen to the auto-click being written
+    pub latency_us: Option<u64>,
+    /// Write error, if the auto-click could not be sent
+    pub error: Option<String>,
+}
+
+impl TriggerRecord {
+    /// Format a timestamp the way records store them
+    pub fn format_time(time: SystemTime) -> String {
+        DateTime::<Local>::from(time).to_rfc3339_opts(SecondsFormat::Micros, false)
+    }
+}
+
+/// Append-only writer for the event log
+pub struct EventLog {
+    writer: BufWriter<File>,
+}
+
+impl EventLog {
+    pub fn open(path: &Path) -> Result<Self, DoubleTapError> {
+        let map_err = |e: std::io::Error| DoubleTapError::EventLog(format!("{}: {}", path.display(), e));
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(map_err)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(map_err)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Append one record and flush it, so the log survives a crash
+    pub fn write(&mut self, record: &TriggerRecord) -> Result<(), DoubleTapError> {
+        serde_json::to_writer(&mut self.writer, record)
+            .map_err(|e| DoubleTapError::EventLog(e.to_string()))?;
+        self.writer
+            .write_all(b"\n")
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| DoubleTapError::EventLog(e.to_string()))
+    }
+}
@@ -0,0 +1,100 @@
+//! Daemon state-change events
+//!
+//! The main loop publishes events here; background consumers (desktop
+//! notifications, user hooks) subscribe and handle them on their own threads
+//! so they never delay the click path.
+
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+
+/// Kinds of events, for per-kind filtering and rate limiting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// The daemon started or stopped
+    Lifecycle,
+    /// Auto-clicking was enabled or disabled
+    Toggle,
+    /// The game window gained or lost focus
+    Focus,
+    /// The virtual device failed to send an auto-click
+    DeviceError,
+}
+
+/// A state change worth telling the user about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DaemonEvent {
+    /// The daemon is up and the virtual device is ready
+    Started,
+    /// The daemon is shutting down
+    Stopped,
+    /// Auto-clicking was enabled (true) or disabled (false)
+    Enabled(bool),
+    /// The game window gained (true) or lost (false) focus
+    Focus(bool),
+    /// Sending an auto-click through the virtual device failed
+    DeviceError(String),
+}
+
+impl DaemonEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::Started | Self::Stopped => EventKind::Lifecycle,
+            Self::Enabled(_) => EventKind::Toggle,
+            Self::Focus(_) => EventKind::Focus,
+            Self::DeviceError(_) => EventKind::DeviceError,
+        }
+    }
+
+    /// Event name, as used for hook keys and `DOUBLETAP_EVENT`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Started => "start",
+            Self::Stopped => "stop",
+            Self::Enabled(true) => "enabled",
+            Self::Enabled(false) => "disabled",
+            Self::Focus(true) => "focus_gained",
+            Self::Focus(false) => "focus_lost",
+            Self::DeviceError(_) => "click_failed",
+        }
+    }
+
+    /// Short human-readable description
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Started => "DoubleTap-RL started".to_string(),
+            Self::Stopped => "DoubleTap-RL stopped".to_string(),
+            Self::Enabled(true) => "Auto-click enabled".to_string(),
+            Self::Enabled(false) => "Auto-click disabled".to_string(),
+            Self::Focus(true) => "Rocket League focused — auto-click active".to_string(),
+            Self::Focus(false) => "Rocket League lost focus — auto-click inactive".to_string(),
+            Self::DeviceError(error) => format!("Virtual device error: {}", error),
+        }
+    }
+}
+
+/// Fan-out of daemon events to any number of subscribers
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<DaemonEvent>>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new subscriber
+    pub fn subscribe(&self) -> mpsc::Receiver<DaemonEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Send an event to every subscriber, dropping ones that have gone away
+    pub fn publish(&self, event: DaemonEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
@@ -4,22 +4,46 @@
 //! and XWayland windows (like Proton/Wine games).
 
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
+use tracing::info;
+use x11rb::errors::ReplyError;
+use x11rb::protocol::xproto::{Atom, Window};
+use x11rb::rust_connection::RustConnection;
 
-
+use crate::launcher::is_in_process_tree;
 use crate::DoubleTapError;
 
 /// Trait for focus detection implementations
 pub trait FocusDetector: Send + Sync {
     /// Check if the target window is currently focused
     fn is_target_focused(&self) -> bool;
+
+    /// Check if the pointer is within the target window
+    ///
+    /// Backends that can't tell (or where the game always covers the screen)
+    /// report true.
+    fn is_pointer_in_target(&self) -> bool {
+        true
+    }
+}
+
+/// Shared detectors, so a restarted poller can reuse the same one
+impl FocusDetector for Arc<dyn FocusDetector> {
+    fn is_target_focused(&self) -> bool {
+        (**self).is_target_focused()
+    }
+
+    fn is_pointer_in_target(&self) -> bool {
+        (**self).is_pointer_in_target()
+    }
 }
 
 /// X11-based focus detector using _NET_ACTIVE_WINDOW
 pub struct X11FocusDetector {
     target_name: String,
+    pointer: PointerTracker,
 }
 
 impl X11FocusDetector {
@@ -29,7 +53,10 @@ impl X11FocusDetector {
         
         // Test connection to X11
         match x11rb::connect(None) {
-            Ok(_) => Ok(Self { target_name }),
+            Ok(_) => Ok(Self {
+                target_name,
+                pointer: PointerTracker::default(),
+            }),
             Err(e) => {
                 Err(DoubleTapError::FocusDetection(format!(
                     "Failed to connect to X11: {}",
@@ -42,19 +69,13 @@ impl X11FocusDetector {
     /// Get the active window title from X11
     fn get_active_window_title(&self) -> Option<String> {
         use x11rb::connection::Connection;
-        use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, Window};
+        use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
         
         let (conn, screen_num) = x11rb::connect(None).ok()?;
         let screen = &conn.setup().roots[screen_num];
         let root = screen.root;
         
         // Intern atoms we need
-        let net_active_window = conn
-            .intern_atom(false, b"_NET_ACTIVE_WINDOW")
-            .ok()?
-            .reply()
-            .ok()?
-            .atom;
         let net_wm_name = conn
             .intern_atom(false, b"_NET_WM_NAME")
             .ok()?
@@ -68,27 +89,7 @@ impl X11FocusDetector {
             .ok()?
             .atom;
         
-        // Get active window
-        let active_window_reply = conn
-            .get_property(false, root, net_active_window, AtomEnum::WINDOW, 0, 1)
-            .ok()?
-            .reply()
-            .ok()?;
-        
-        if active_window_reply.value.len() < 4 {
-            return None;
-        }
-        
-        let active_window = u32::from_ne_bytes([
-            active_window_reply.value[0],
-            active_window_reply.value[1],
-            active_window_reply.value[2],
-            active_window_reply.value[3],
-        ]) as Window;
-        
-        if active_window == 0 {
-            return None;
-        }
+        let active_window = get_active_window(&conn, root)?;
         
         // Try _NET_WM_NAME first (UTF-8)
         let name_reply = conn
@@ -131,6 +132,359 @@ impl FocusDetector for X11FocusDetector {
             false
         }
     }
+
+    fn is_pointer_in_target(&self) -> bool {
+        self.pointer.is_pointer_in_active_window()
+    }
+}
+
+/// PID-based focus detector for the Steam launch wrapper
+///
+/// Matches the active window's _NET_WM_PID against the process tree of the
+/// game we spawned, so the window title doesn't matter.
+pub struct PidFocusDetector {
+    root_pid: u32,
+    pointer: PointerTracker,
+}
+
+impl PidFocusDetector {
+    /// Create a new PID focus detector for the process tree rooted at `root_pid`
+    pub fn new(root_pid: u32) -> Result<Self, DoubleTapError> {
+        match x11rb::connect(None) {
+            Ok(_) => Ok(Self {
+                root_pid,
+                pointer: PointerTracker::default(),
+            }),
+            Err(e) => Err(DoubleTapError::FocusDetection(format!(
+                "Failed to connect to X11: {}",
+                e
+            ))),
+        }
+    }
+
+    /// Get the PID owning the active window
+    fn get_active_window_pid(&self) -> Option<u32> {
+        use x11rb::connection::Connection;
+
+        let (conn, screen_num) = x11rb::connect(None).ok()?;
+        let root = conn.setup().roots[screen_num].root;
+        let active_window = get_active_window(&conn, root)?;
+        let net_wm_pid = intern_existing_atom(&conn, b"_NET_WM_PID")?;
+        read_cardinal(&conn, active_window, net_wm_pid)
+    }
+}
+
+impl FocusDetector for PidFocusDetector {
+    fn is_target_focused(&self) -> bool {
+        self.get_active_window_pid()
+            .is_some_and(|pid| is_in_process_tree(pid, self.root_pid))
+    }
+
+    fn is_pointer_in_target(&self) -> bool {
+        self.pointer.is_pointer_in_active_window()
+    }
+}
+
+/// Steam AppID of Rocket League
+pub const ROCKET_LEAGUE_APP_ID: u32 = 252950;
+
+/// Gamescope-based focus detector using GAMESCOPE_FOCUSED_APP
+///
+/// Under gamescope (Steam Deck, Big Picture sessions) the game runs in a
+/// nested XWayland where _NET_ACTIVE_WINDOW doesn't reflect what's on screen.
+/// Gamescope publishes the focused Steam AppID on its root window instead.
+///
+/// The atoms only exist on gamescope's own XWayland, so `DISPLAY` must point
+/// at it. Launched from the Steam Deck's game mode or from inside the
+/// gamescope session that's already the case; a daemon started from a
+/// desktop session next to a nested gamescope sees the desktop's X server,
+/// finds no atoms, and falls back to the regular backends.
+pub struct GamescopeFocusDetector {
+    app_id: u32,
+}
+
+impl GamescopeFocusDetector {
+    /// Create a new gamescope focus detector for the given Steam AppID
+    pub fn new(app_id: u32) -> Result<Self, DoubleTapError> {
+        if is_gamescope_session() {
+            Ok(Self { app_id })
+        } else {
+            Err(DoubleTapError::FocusDetection(
+                "Not running under gamescope".to_string(),
+            ))
+        }
+    }
+
+    /// Get the Steam AppID gamescope currently has focused
+    fn get_focused_app_id(&self) -> Option<u32> {
+        use x11rb::connection::Connection;
+
+        let (conn, screen_num) = x11rb::connect(None).ok()?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let focused_app = intern_existing_atom(&conn, b"GAMESCOPE_FOCUSED_APP")?;
+        let app_id = read_cardinal(&conn, root, focused_app);
+        resolve_focused_app(app_id, || {
+            let focused_window = intern_existing_atom(&conn, b"GAMESCOPE_FOCUSED_WINDOW")?;
+            let steam_game = intern_existing_atom(&conn, b"STEAM_GAME")?;
+            let window = read_cardinal(&conn, root, focused_window).filter(|&w| w != 0)?;
+            read_cardinal(&conn, window, steam_game)
+        })
+    }
+}
+
+impl FocusDetector for GamescopeFocusDetector {
+    fn is_target_focused(&self) -> bool {
+        self.get_focused_app_id() == Some(self.app_id)
+    }
+}
+
+/// The focused Steam AppID from gamescope's root `GAMESCOPE_FOCUSED_APP`,
+/// or else `window_steam_game`, the focused window's `STEAM_GAME`
+///
+/// Gamescope writes 0 while nothing with an AppID has focus, so 0 counts as
+/// unset. The fallback only runs when needed, since it costs X round trips.
+fn resolve_focused_app(
+    focused_app: Option<u32>,
+    window_steam_game: impl FnOnce() -> Option<u32>,
+) -> Option<u32> {
+    focused_app.filter(|&id| id != 0).or_else(window_steam_game)
+}
+
+/// Check whether the X server we're connected to is gamescope's XWayland
+pub fn is_gamescope_session() -> bool {
+    use x11rb::connection::Connection;
+
+    let Ok((conn, screen_num)) = x11rb::connect(None) else {
+        return false;
+    };
+    let root = conn.setup().roots[screen_num].root;
+
+    intern_existing_atom(&conn, b"GAMESCOPE_FOCUSED_APP")
+        .and_then(|atom| read_cardinal(&conn, root, atom))
+        .is_some()
+}
+
+/// Whether the pointer at `pointer` (root coordinates) is inside a window
+/// at `origin` of the given `size`
+///
+/// Fullscreen windows always count as containing the pointer.
+fn contains_pointer(fullscreen: bool, origin: (i16, i16), size: (u16, u16), pointer: (i16, i16)) -> bool {
+    if fullscreen {
+        return true;
+    }
+    let x = i32::from(pointer.0) - i32::from(origin.0);
+    let y = i32::from(pointer.1) - i32::from(origin.1);
+    x >= 0 && y >= 0 && x < i32::from(size.0) && y < i32::from(size.1)
+}
+
+/// The X11 connection and atoms the pointer gate queries on every poll
+struct PointerProbe {
+    conn: RustConnection,
+    root: Window,
+    net_active_window: Atom,
+    net_wm_state: Atom,
+    fullscreen: Atom,
+}
+
+impl PointerProbe {
+    fn connect() -> Option<Self> {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::ConnectionExt;
+
+        let (conn, screen_num) = x11rb::connect(None).ok()?;
+        let root = conn.setup().roots[screen_num].root;
+        let intern = |name: &[u8]| Some(conn.intern_atom(false, name).ok()?.reply().ok()?.atom);
+        Some(Self {
+            net_active_window: intern(b"_NET_ACTIVE_WINDOW")?,
+            net_wm_state: intern(b"_NET_WM_STATE")?,
+            fullscreen: intern(b"_NET_WM_STATE_FULLSCREEN")?,
+            root,
+            conn,
+        })
+    }
+
+    /// Whether the pointer is inside the active window; None without one
+    fn is_pointer_in_active_window(&self) -> Result<Option<bool>, ReplyError> {
+        use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+
+        let conn = &self.conn;
+        let active = conn
+            .get_property(false, self.root, self.net_active_window, AtomEnum::WINDOW, 0, 1)?
+            .reply()?;
+        let Some(window) = active.value32().and_then(|mut windows| windows.next()).filter(|&w| w != 0) else {
+            return Ok(None);
+        };
+
+        let state = conn
+            .get_property(false, window, self.net_wm_state, AtomEnum::ATOM, 0, 32)?
+            .reply()?;
+        let fullscreen = state
+            .value32()
+            .is_some_and(|mut atoms| atoms.any(|atom| atom == self.fullscreen));
+        let geometry = conn.get_geometry(window)?.reply()?;
+        let origin = conn.translate_coordinates(window, self.root, 0, 0)?.reply()?;
+        let pointer = conn.query_pointer(self.root)?.reply()?;
+
+        Ok(Some(contains_pointer(
+            fullscreen,
+            (origin.dst_x, origin.dst_y),
+            (geometry.width, geometry.height),
+            (pointer.root_x, pointer.root_y),
+        )))
+    }
+}
+
+/// Answers the pointer gate over one X11 connection, opened on first use
+/// and again only after it broke
+#[derive(Default)]
+struct PointerTracker {
+    probe: Mutex<Option<PointerProbe>>,
+}
+
+impl PointerTracker {
+    /// Whether the pointer is inside the active window; false when that
+    /// can't be told
+    fn is_pointer_in_active_window(&self) -> bool {
+        let mut probe = self.probe.lock().unwrap();
+        if probe.is_none() {
+            *probe = PointerProbe::connect();
+        }
+        let Some(connected) = probe.as_ref() else {
+            return false;
+        };
+        match connected.is_pointer_in_active_window() {
+            Ok(inside) => inside.unwrap_or(false),
+            Err(ReplyError::ConnectionError(_)) => {
+                *probe = None;
+                false
+            }
+            // e.g. the window closed between the queries
+            Err(_) => false,
+        }
+    }
+}
+
+/// Get the window EWMH reports as active (_NET_ACTIVE_WINDOW)
+fn get_active_window(
+    conn: &impl x11rb::connection::Connection,
+    root: x11rb::protocol::xproto::Window,
+) -> Option<x11rb::protocol::xproto::Window> {
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+
+    let net_active_window = conn
+        .intern_atom(false, b"_NET_ACTIVE_WINDOW")
+        .ok()?
+        .reply()
+        .ok()?
+        .atom;
+
+    let reply = conn
+        .get_property(false, root, net_active_window, AtomEnum::WINDOW, 0, 1)
+        .ok()?
+        .reply()
+        .ok()?;
+    let active_window = reply.value32()?.next().filter(|&window| window != 0);
+    active_window
+}
+
+/// Whether the window manager reports an active window (_NET_ACTIVE_WINDOW),
+/// which the title and PID backends rely on
+pub fn has_active_window() -> bool {
+    use x11rb::connection::Connection;
+
+    let Ok((conn, screen_num)) = x11rb::connect(None) else {
+        return false;
+    };
+    let root = conn.setup().roots[screen_num].root;
+    get_active_window(&conn, root).is_some()
+}
+
+/// Titles of the managed windows currently shown on screen
+/// (_NET_CLIENT_LIST)
+pub fn visible_window_titles() -> Result<Vec<String>, DoubleTapError> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, MapState};
+
+    let (conn, screen_num) = x11rb::connect(None)
+        .map_err(|e| DoubleTapError::FocusDetection(format!("Failed to connect to X11: {}", e)))?;
+    let root = conn.setup().roots[screen_num].root;
+    let client_list = intern_existing_atom(&conn, b"_NET_CLIENT_LIST").ok_or_else(|| {
+        DoubleTapError::FocusDetection("Window manager doesn't publish _NET_CLIENT_LIST".to_string())
+    })?;
+    let windows: Vec<u32> = conn
+        .get_property(false, root, client_list, AtomEnum::WINDOW, 0, 1024)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .and_then(|reply| reply.value32().map(|windows| windows.collect()))
+        .unwrap_or_default();
+
+    Ok(windows
+        .into_iter()
+        .filter(|&window| {
+            conn.get_window_attributes(window)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+                .is_some_and(|attributes| attributes.map_state == MapState::VIEWABLE)
+        })
+        .filter_map(|window| read_window_title(&conn, window))
+        .collect())
+}
+
+/// A window's title: _NET_WM_NAME, falling back to the legacy WM_NAME
+fn read_window_title(
+    conn: &impl x11rb::connection::Connection,
+    window: x11rb::protocol::xproto::Window,
+) -> Option<String> {
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+
+    let net_wm_name = intern_existing_atom(conn, b"_NET_WM_NAME");
+    let utf8_string = intern_existing_atom(conn, b"UTF8_STRING");
+    if let (Some(net_wm_name), Some(utf8_string)) = (net_wm_name, utf8_string) {
+        let reply = conn
+            .get_property(false, window, net_wm_name, utf8_string, 0, 256)
+            .ok()?
+            .reply()
+            .ok()?;
+        if !reply.value.is_empty() {
+            return String::from_utf8(reply.value).ok();
+        }
+    }
+
+    let reply = conn
+        .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 256)
+        .ok()?
+        .reply()
+        .ok()?;
+    (!reply.value.is_empty()).then(|| String::from_utf8_lossy(&reply.value).into_owned())
+}
+
+/// Look up an atom without creating it, returning None if it doesn't exist
+fn intern_existing_atom(
+    conn: &impl x11rb::connection::Connection,
+    name: &[u8],
+) -> Option<x11rb::protocol::xproto::Atom> {
+    use x11rb::protocol::xproto::ConnectionExt;
+
+    let atom = conn.intern_atom(true, name).ok()?.reply().ok()?.atom;
+    (atom != x11rb::NONE).then_some(atom)
+}
+
+/// Read the first CARDINAL value of a window property
+fn read_cardinal(
+    conn: &impl x11rb::connection::Connection,
+    window: x11rb::protocol::xproto::Window,
+    property: x11rb::protocol::xproto::Atom,
+) -> Option<u32> {
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+
+    let reply = conn
+        .get_property(false, window, property, AtomEnum::CARDINAL, 0, 1)
+        .ok()?
+        .reply()
+        .ok()?;
+    let value = reply.value32()?.next();
+    value
 }
 
 /// Cached focus detector that wraps another detector
@@ -168,12 +522,40 @@ impl<D: FocusDetector> FocusDetector for CachedFocusDetector<D> {
             self.cached_state.load(Ordering::SeqCst)
         }
     }
+
+    fn is_pointer_in_target(&self) -> bool {
+        self.inner.is_pointer_in_target()
+    }
 }
 
 /// Create the focus detector
+///
+/// Prefers the gamescope backend (matched by Steam AppID) when running under
+/// gamescope. Otherwise matches the active window's PID against `target_pid`
+/// when the game was spawned by the launch wrapper, and falls back to EWMH
+/// window-title matching.
 pub fn create_focus_detector(
     target_window: &str,
+    target_app_id: u32,
+    target_pid: Option<u32>,
 ) -> Result<Box<dyn FocusDetector>, DoubleTapError> {
+    if let Ok(detector) = GamescopeFocusDetector::new(target_app_id) {
+        info!("Gamescope session detected, matching Steam AppID {}", target_app_id);
+        return Ok(Box::new(CachedFocusDetector::new(
+            detector,
+            Duration::from_millis(100),
+        )));
+    }
+
+    if let Some(pid) = target_pid {
+        info!("Matching focus by process tree of PID {}", pid);
+        let detector = PidFocusDetector::new(pid)?;
+        return Ok(Box::new(CachedFocusDetector::new(
+            detector,
+            Duration::from_millis(100),
+        )));
+    }
+
     let detector = X11FocusDetector::new(target_window)?;
     Ok(Box::new(CachedFocusDetector::new(
         detector,
@@ -181,15 +563,26 @@ pub fn create_focus_detector(
     )))
 }
 
+/// Detector that always reports the target as focused, for replays
+pub struct AlwaysFocused;
+
+impl FocusDetector for AlwaysFocused {
+    fn is_target_focused(&self) -> bool {
+        true
+    }
+}
+
 /// Shared focus state that can be polled from another thread
 pub struct FocusState {
     is_focused: AtomicBool,
+    is_pointer_inside: AtomicBool,
 }
 
 impl FocusState {
     pub fn new() -> Self {
         Self {
             is_focused: AtomicBool::new(false),
+            is_pointer_inside: AtomicBool::new(true),
         }
     }
     
@@ -197,9 +590,19 @@ impl FocusState {
         self.is_focused.load(Ordering::SeqCst)
     }
     
-    fn set_focused(&self, focused: bool) {
+    /// Whether the pointer is within the focused window (always true when
+    /// the pointer gate is disabled)
+    pub fn is_pointer_inside(&self) -> bool {
+        self.is_pointer_inside.load(Ordering::SeqCst)
+    }
+    
+    pub(crate) fn set_focused(&self, focused: bool) {
         self.is_focused.store(focused, Ordering::SeqCst);
     }
+    
+    fn set_pointer_inside(&self, inside: bool) {
+        self.is_pointer_inside.store(inside, Ordering::SeqCst);
+    }
 }
 
 impl Default for FocusState {
@@ -209,16 +612,66 @@ impl Default for FocusState {
 }
 
 /// Start a background thread that polls focus state
+///
+/// With `gate_pointer` set, the poller also tracks whether the pointer is
+/// inside the focused window.
 pub fn start_focus_poller(
     detector: Box<dyn FocusDetector>,
     state: Arc<FocusState>,
     running: Arc<AtomicBool>,
+    gate_pointer: bool,
 ) -> thread::JoinHandle<()> {
     thread::spawn(move || {
         while running.load(Ordering::SeqCst) {
             let focused = detector.is_target_focused();
             state.set_focused(focused);
+            if gate_pointer && focused {
+                state.set_pointer_inside(detector.is_pointer_in_target());
+            }
             thread::sleep(Duration::from_millis(100));
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_pointer_is_inside_within_the_window_bounds() {
+        let origin = (100, 50);
+        let size = (800, 600);
+        assert!(contains_pointer(false, origin, size, (100, 50)));
+        assert!(contains_pointer(false, origin, size, (899, 649)));
+        assert!(!contains_pointer(false, origin, size, (900, 300)));
+        assert!(!contains_pointer(false, origin, size, (500, 650)));
+        assert!(!contains_pointer(false, origin, size, (99, 300)));
+        assert!(!contains_pointer(false, origin, size, (-1920, 300)));
+    }
+
+    #[test]
+    fn fullscreen_windows_always_contain_the_pointer() {
+        assert!(contains_pointer(true, (0, 0), (1920, 1080), (2500, 300)));
+        assert!(contains_pointer(true, (0, 0), (0, 0), (0, 0)));
+    }
+
+    #[test]
+    fn windows_on_a_monitor_left_of_the_origin_work() {
+        assert!(contains_pointer(false, (-1920, 0), (1920, 1080), (-1, 1079)));
+        assert!(!contains_pointer(false, (-1920, 0), (1920, 1080), (0, 0)));
+    }
+
+    #[test]
+    fn the_root_app_id_wins_without_asking_the_window() {
+        let app = resolve_focused_app(Some(ROCKET_LEAGUE_APP_ID), || panic!("fallback queried"));
+        assert_eq!(app, Some(ROCKET_LEAGUE_APP_ID));
+        assert_ne!(resolve_focused_app(Some(730), || None), Some(ROCKET_LEAGUE_APP_ID));
+    }
+
+    #[test]
+    fn a_zero_or_missing_app_id_falls_back_to_steam_game() {
+        assert_eq!(resolve_focused_app(Some(0), || Some(ROCKET_LEAGUE_APP_ID)), Some(ROCKET_LEAGUE_APP_ID));
+        assert_eq!(resolve_focused_app(None, || Some(ROCKET_LEAGUE_APP_ID)), Some(ROCKET_LEAGUE_APP_ID));
+        assert_eq!(resolve_focused_app(Some(0), || None), None);
+    }
+}
@@ -0,0 +1,299 @@
+//! Match-state tracking by tailing Rocket League's Launch.log
+//!
+//! The game logs every map load to `TAGame/Logs/Launch.log` inside its Proton
+//! prefix. The `LoadMap:` URL tells us whether we're in the main menu, a
+//! match, training/freeplay or a replay.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::focus_detector::ROCKET_LEAGUE_APP_ID;
+
+/// Launch.log location relative to the game's Proton prefix
+const LAUNCH_LOG_IN_PREFIX: &str =
+    "pfx/drive_c/users/steamuser/Documents/My Games/Rocket League/TAGame/Logs/Launch.log";
+
+/// Steam install roots to search, relative to $HOME
+const STEAM_ROOTS: &[&str] = &[
+    ".local/share/Steam",
+    ".steam/steam",
+    ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+];
+
+/// How often the log is checked for new lines
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What the game is currently doing, as far as the log tells us
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum GamePhase {
+    /// No map load seen yet (or no log found)
+    Unknown = 0,
+    /// Main menu / garage
+    Menu = 1,
+    /// Online or private match
+    Match = 2,
+    /// Freeplay, training packs and workshop maps
+    Training = 3,
+    /// Replay viewer
+    Replay = 4,
+}
+
+impl GamePhase {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Menu,
+            2 => Self::Match,
+            3 => Self::Training,
+            4 => Self::Replay,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Classify a Launch.log line, returning the new phase for map loads
+    pub fn from_log_line(line: &str) -> Option<Self> {
+        let (_, url) = line.split_once("LoadMap: ")?;
+
+        if url.starts_with("MENU_") || url.contains("/MENU_") {
+            Some(Self::Menu)
+        } else if url.contains("GameInfo_Replay_TA") || url.contains("?Demo") {
+            Some(Self::Replay)
+        } else if url.contains("GameInfo_Tutorial_TA")
+            || url.contains("GameInfo_Training")
+            || url.contains("GameInfo_GameEditor_Training")
+        {
+            Some(Self::Training)
+        } else {
+            Some(Self::Match)
+        }
+    }
+
+    /// Whether this phase is one of `allowed`
+    ///
+    /// An empty list or an unknown phase allows everything, so a missing log
+    /// never blocks auto-clicks.
+    pub fn is_allowed_by(self, allowed: &[GamePhase]) -> bool {
+        allowed.is_empty() || self == GamePhase::Unknown || allowed.contains(&self)
+    }
+}
+
+/// Shared game phase that can be polled from another thread
+pub struct GameState {
+    phase: AtomicU8,
+}
+
+impl GameState {
+    pub fn new() -> Self {
+        Self {
+            phase: AtomicU8::new(GamePhase::Unknown as u8),
+        }
+    }
+
+    pub fn phase(&self) -> GamePhase {
+        GamePhase::from_u8(self.phase.load(Ordering::SeqCst))
+    }
+
+    fn set_phase(&self, phase: GamePhase) {
+        let previous = self.phase.swap(phase as u8, Ordering::SeqCst);
+        if previous != phase as u8 {
+            info!("Game phase: {:?}", phase);
+        }
+    }
+
+    /// Whether the current phase is one of `allowed`
+    pub fn is_allowed(&self, allowed: &[GamePhase]) -> bool {
+        self.phase().is_allowed_by(allowed)
+    }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Search the Steam libraries for Rocket League's Launch.log
+pub fn find_launch_log() -> Option<PathBuf> {
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+
+    STEAM_ROOTS
+        .iter()
+        .map(|root| home.join(root))
+        .flat_map(|root| steam_library_paths(&root))
+        .map(|library| {
+            library
+                .join("steamapps/compatdata")
+                .join(ROCKET_LEAGUE_APP_ID.to_string())
+                .join(LAUNCH_LOG_IN_PREFIX)
+        })
+        .find(|path| path.exists())
+}
+
+/// Steam library folders listed in `libraryfolders.vdf`, plus the root itself
+fn steam_library_paths(steam_root: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![steam_root.to_path_buf()];
+
+    let vdf = steam_root.join("steamapps/libraryfolders.vdf");
+    if let Ok(contents) = std::fs::read_to_string(vdf) {
+        for line in contents.lines() {
+            let mut fields = line.split('"').filter(|field| !field.trim().is_empty());
+            if fields.next() == Some("path") {
+                if let Some(path) = fields.next() {
+                    libraries.push(PathBuf::from(path.replace("\\\\", "\\")));
+                }
+            }
+        }
+    }
+
+    libraries
+}
+
+/// Whether the file at `path` is no longer the one we have open
+fn is_replaced(path: &Path, file: &File) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (std::fs::metadata(path), file.metadata()) {
+        (Ok(on_disk), Ok(open)) => on_disk.ino() != open.ino() || on_disk.dev() != open.dev(),
+        (Err(_), _) => false,
+        (_, Err(_)) => true,
+    }
+}
+
+/// Start a background thread that tails Launch.log and updates the phase
+///
+/// Uses `log_path` when given, otherwise searches the Steam libraries. The
+/// log is re-read from the start whenever it shrinks or is replaced (a new
+/// game launch).
+pub fn start_game_state_tailer(
+    log_path: Option<PathBuf>,
+    state: Arc<GameState>,
+    running: Arc<AtomicBool>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader: Option<BufReader<File>> = None;
+        let mut current_path = PathBuf::new();
+        let mut position = 0u64;
+        let mut warned = false;
+        let mut line = Vec::new();
+
+        while running.load(Ordering::SeqCst) {
+            if reader.is_none() {
+                let path = log_path.clone().or_else(find_launch_log);
+                match path.as_deref().map(File::open) {
+                    Some(Ok(file)) => {
+                        current_path = path.unwrap_or_default();
+                        info!("Tailing game log: {:?}", current_path);
+                        reader = Some(BufReader::new(file));
+                        position = 0;
+                        warned = false;
+                    }
+                    _ if !warned => {
+                        warn!("Launch.log not found yet, game phase unknown");
+                        warned = true;
+                    }
+                    _ => {}
+                }
+            }
+
+            if reader.as_ref().is_some_and(|file| is_replaced(&current_path, file.get_ref())) {
+                debug!("Launch.log replaced, reopening");
+                reader = None;
+                state.set_phase(GamePhase::Unknown);
+                continue;
+            }
+
+            if let Some(file) = reader.as_mut() {
+                // A shorter file means the game restarted and rewrote the log
+                let len = file.get_ref().metadata().map(|m| m.len()).unwrap_or(0);
+                if len < position {
+                    debug!("Launch.log truncated, re-reading");
+                    position = file.seek(SeekFrom::Start(0)).unwrap_or(0);
+                    state.set_phase(GamePhase::Unknown);
+                }
+
+                loop {
+                    line.clear();
+                    match file.read_until(b'\n', &mut line) {
+                        Ok(0) => break,
+                        Ok(n) if line.ends_with(b"\n") => {
+                            position += n as u64;
+                            let text = String::from_utf8_lossy(&line);
+                            if let Some(phase) = GamePhase::from_log_line(&text) {
+                                state.set_phase(phase);
+                            }
+                        }
+                        Ok(_) => {
+                            // Partial line — rewind and wait for the rest
+                            let _ = file.seek(SeekFrom::Start(position));
+                            break;
+                        }
+                        Err(e) => {
+                            warn!("Failed to read Launch.log: {}", e);
+                            reader = None;
+                            break;
+                        }
+                    }
+                }
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_map_loads() {
+        let lines = [
+            ("[0004.12] Log: LoadMap: MENU_Main_p?closed", GamePhase::Menu),
+            (
+                "[0312.88] Log: LoadMap: /Game/Maps/MENU_Main_p?Game=TAGame.GameInfo_Menu_TA",
+                GamePhase::Menu,
+            ),
+            (
+                "[0150.03] Log: LoadMap: Stadium_P?Game=TAGame.GameInfo_Soccar_TA?Playlist=6?MaxPlayers=8",
+                GamePhase::Match,
+            ),
+            (
+                "[0210.40] Log: LoadMap: Park_P?Game=TAGame.GameInfo_Tutorial_TA?Freeplay?MaxPlayers=1",
+                GamePhase::Training,
+            ),
+            (
+                "[0260.51] Log: LoadMap: EuroStadium_P?Game=TAGame.GameInfo_GameEditor_Training_TA?Playlist=20",
+                GamePhase::Training,
+            ),
+            (
+                "[0400.77] Log: LoadMap: Stadium_P?Game=TAGame.GameInfo_Replay_TA",
+                GamePhase::Replay,
+            ),
+            ("[0401.00] Log: LoadMap: Stadium_P?Demo", GamePhase::Replay),
+        ];
+        for (line, phase) in lines {
+            assert_eq!(GamePhase::from_log_line(line), Some(phase), "{}", line);
+        }
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        for line in [
+            "",
+            "[0000.01] Log: Log file open, 10/18/26 21:04:11",
+            "[0150.10] Log: Browse: Stadium_P?Game=TAGame.GameInfo_Soccar_TA",
+            "[0151.20] ScriptLog: Loaded map Stadium_P",
+            "LoadMap Stadium_P",
+        ] {
+            assert_eq!(GamePhase::from_log_line(line), None, "{}", line);
+        }
+    }
+}
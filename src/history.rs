@@ -0,0 +1,460 @@
+//! Statistics history across sessions
+//!
+//! The daemon appends one JSON line per recording interval to
+//! `$XDG_DATA_HOME/doubletap-rl/history.jsonl` (usually
+//! `~/.local/share/doubletap-rl/history.jsonl`), holding the counts since the
+//! previous line together with the active profile and game. `doubletap-rl
+//! stats` reads the file back and summarises it per day, profile and game.
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+use crate::stats::{SessionStats, StatsSnapshot};
+use crate::DoubleTapError;
+
+/// How often the running daemon appends a record
+pub const RECORD_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Counters kept in the history
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Counts {
+    pub triggers: u64,
+    pub auto_clicks: u64,
+    pub natural_double_taps: u64,
+    pub suppressed: u64,
+    pub send_failures: u64,
+}
+
+impl Counts {
+    fn from_snapshot(snapshot: &StatsSnapshot) -> Self {
+        Self {
+            triggers: snapshot.triggers,
+            auto_clicks: snapshot.auto_clicks,
+            natural_double_taps: snapshot.natural_double_taps,
+            suppressed: snapshot.suppressed_disabled
+                + snapshot.suppressed_focus
+                + snapshot.suppressed_gate
+                + snapshot.suppressed_feedback
+                + snapshot.suppressed_rate_limit,
+            send_failures: snapshot.send_failures,
+        }
+    }
+
+    /// Counts accumulated since `earlier`
+    fn since(&self, earlier: &Counts) -> Self {
+        Self {
+            triggers: self.triggers.saturating_sub(earlier.triggers),
+            auto_clicks: self.auto_clicks.saturating_sub(earlier.auto_clicks),
+            natural_double_taps: self
+                .natural_double_taps
+                .saturating_sub(earlier.natural_double_taps),
+            suppressed: self.suppressed.saturating_sub(earlier.suppressed),
+            send_failures: self.send_failures.saturating_sub(earlier.send_failures),
+        }
+    }
+
+    fn add(&mut self, other: &Counts) {
+        self.triggers += other.triggers;
+        self.auto_clicks += other.auto_clicks;
+        self.natural_double_taps += other.natural_double_taps;
+        self.suppressed += other.suppressed;
+        self.send_failures += other.send_failures;
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// One line of the history file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    /// Interval start and end, in seconds since the Unix epoch
+    pub start: i64,
+    pub end: i64,
+    pub profile: String,
+    pub game: String,
+    #[serde(flatten)]
+    pub counts: Counts,
+}
+
+/// Default history file path
+pub fn default_path() -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        })?;
+    Some(data_home.join("doubletap-rl").join("history.jsonl"))
+}
+
+/// Name the history files the session's counts under
+///
+/// Steam sets `SteamAppId` for games it launches (including through the
+/// launch wrapper); otherwise the target window title is used.
+pub fn game_label(target_window: &str) -> String {
+    match std::env::var("SteamAppId") {
+        Ok(app_id) if !app_id.is_empty() && app_id != "0" => format!("steam:{}", app_id),
+        _ => target_window.to_string(),
+    }
+}
+
+/// Appends the running session's counts to the history file
+pub struct HistoryRecorder {
+    path: PathBuf,
+    game: String,
+    profile: String,
+    /// Session totals at the last record
+    recorded: Counts,
+    interval_start: i64,
+    last_record: Instant,
+}
+
+impl HistoryRecorder {
+    pub fn new(path: PathBuf, game: String, profile: &str) -> Self {
+        info!("Statistics history: {:?}", path);
+        Self {
+            path,
+            game,
+            profile: profile.to_string(),
+            recorded: Counts::default(),
+            interval_start: Local::now().timestamp(),
+            last_record: Instant::now(),
+        }
+    }
+
+    /// Record the counts if the interval has passed or the profile changed,
+    /// so each record is attributed to a single profile
+    pub fn update(&mut self, stats: &SessionStats, profile: &str) -> Result<(), DoubleTapError> {
+        if self.profile == profile && self.last_record.elapsed() < RECORD_INTERVAL {
+            return Ok(());
+        }
+        let result = self.record(stats);
+        self.profile = profile.to_string();
+        result
+    }
+
+    /// Append the counts accumulated since the last record, if any
+    pub fn record(&mut self, stats: &SessionStats) -> Result<(), DoubleTapError> {
+        let totals = Counts::from_snapshot(&stats.snapshot());
+        let counts = totals.since(&self.recorded);
+        let now = Local::now().timestamp();
+        self.last_record = Instant::now();
+
+        if !counts.is_empty() {
+            let record = HistoryRecord {
+                start: self.interval_start,
+                end: now,
+                profile: self.profile.clone(),
+                game: self.game.clone(),
+                counts,
+            };
+            append(&self.path, &record)?;
+            debug!("Recorded statistics: {:?}", record);
+        }
+
+        self.recorded = totals;
+        self.interval_start = now;
+        Ok(())
+    }
+}
+
+fn append(path: &Path, record: &HistoryRecord) -> Result<(), DoubleTapError> {
+    let map_err = |e: std::io::Error| DoubleTapError::History(format!("{}: {}", path.display(), e));
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(map_err)?;
+    }
+    let mut line =
+        serde_json::to_string(record).map_err(|e| DoubleTapError::History(e.to_string()))?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(map_err)
+}
+
+/// Read the records that started within `[since, until)` (Unix seconds)
+///
+/// A missing file is an empty history; malformed lines (e.g. a record cut
+/// short by a crash) are skipped.
+pub fn load(path: &Path, since: i64, until: i64) -> Result<Vec<HistoryRecord>, DoubleTapError> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(DoubleTapError::History(format!("{}: {}", path.display(), e))),
+    };
+
+    let mut records = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| DoubleTapError::History(format!("{}: {}", path.display(), e)))?;
+        match serde_json::from_str::<HistoryRecord>(&line) {
+            Ok(record) if record.start >= since && record.start < until => records.push(record),
+            Ok(_) => {}
+            Err(e) => debug!("Skipping history line {}: {}", number + 1, e),
+        }
+    }
+    Ok(records)
+}
+
+/// Start of a local calendar day, in Unix seconds
+pub fn day_start(date: NaiveDate) -> i64 {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|time| time.timestamp())
+        .unwrap_or_else(|| midnight.and_utc().timestamp())
+}
+
+/// Parse a `YYYY-MM-DD` date (for the `stats` command line)
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|e| format!("expected YYYY-MM-DD: {}", e))
+}
+
+/// First and last day for `stats`: from `since`, or the `days` days up to
+/// and including `last_day`
+pub fn date_range(
+    days: u32,
+    since: Option<NaiveDate>,
+    last_day: NaiveDate,
+) -> Result<(NaiveDate, NaiveDate), DoubleTapError> {
+    let first_day = match since {
+        Some(since) => since,
+        None => last_day
+            .checked_sub_days(chrono::Days::new(u64::from(days.max(1)) - 1))
+            .ok_or_else(|| {
+                DoubleTapError::History(format!("{} days before {} is out of range", days, last_day))
+            })?,
+    };
+    Ok((first_day, last_day))
+}
+
+/// History totals over a date range
+pub struct Summary {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+    pub by_day: BTreeMap<NaiveDate, Counts>,
+    pub by_profile: BTreeMap<String, Counts>,
+    pub by_game: BTreeMap<String, Counts>,
+    pub total: Counts,
+}
+
+impl Summary {
+    /// Summarise the history between two local dates (both inclusive)
+    pub fn load(path: &Path, first_day: NaiveDate, last_day: NaiveDate) -> Result<Self, DoubleTapError> {
+        let until = last_day
+            .succ_opt()
+            .map(day_start)
+            .unwrap_or(i64::MAX);
+        let records = load(path, day_start(first_day), until)?;
+
+        let mut summary = Self {
+            first_day,
+            last_day,
+            by_day: BTreeMap::new(),
+            by_profile: BTreeMap::new(),
+            by_game: BTreeMap::new(),
+            total: Counts::default(),
+        };
+        for record in &records {
+            let day = DateTime::from_timestamp(record.start, 0)
+                .map(|time| time.with_timezone(&Local).date_naive())
+                .unwrap_or(first_day);
+            summary.by_day.entry(day).or_default().add(&record.counts);
+            summary
+                .by_profile
+                .entry(record.profile.clone())
+                .or_default()
+                .add(&record.counts);
+            summary
+                .by_game
+                .entry(record.game.clone())
+                .or_default()
+                .add(&record.counts);
+            summary.total.add(&record.counts);
+        }
+        Ok(summary)
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Statistics from {} to {}", self.first_day, self.last_day)?;
+        if self.total.is_empty() {
+            return write!(f, "No activity recorded");
+        }
+
+        let days = self.by_day.iter().map(|(day, counts)| (day.to_string(), counts));
+        write_table(f, "Day", days)?;
+        write_table(f, "Profile", self.by_profile.iter().map(|(name, c)| (name.clone(), c)))?;
+        write_table(f, "Game", self.by_game.iter().map(|(name, c)| (name.clone(), c)))?;
+        write!(
+            f,
+            "\nTotal: {} auto-clicks, {} natural double-taps, {} triggers",
+            self.total.auto_clicks, self.total.natural_double_taps, self.total.triggers
+        )
+    }
+}
+
+fn write_table<'a>(
+    f: &mut fmt::Formatter<'_>,
+    heading: &str,
+    rows: impl Iterator<Item = (String, &'a Counts)>,
+) -> fmt::Result {
+    writeln!(
+        f,
+        "\n{:<24} {:>11} {:>8} {:>9} {:>10} {:>8}",
+        heading, "auto-clicks", "natural", "triggers", "suppressed", "failures"
+    )?;
+    for (label, counts) in rows {
+        writeln!(
+            f,
+            "{:<24} {:>11} {:>8} {:>9} {:>10} {:>8}",
+            label,
+            counts.auto_clicks,
+            counts.natural_double_taps,
+            counts.triggers,
+            counts.suppressed,
+            counts.send_failures
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A history file of its own for each test
+    struct TempHistory(PathBuf);
+
+    impl TempHistory {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("doubletap-rl-history-{}-{}.jsonl", std::process::id(), name));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempHistory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        parse_date(value).unwrap()
+    }
+
+    fn record(day: &str, hour: i64, profile: &str, auto_clicks: u64) -> HistoryRecord {
+        let start = day_start(date(day)) + hour * 3600;
+        HistoryRecord {
+            start,
+            end: start + 300,
+            profile: profile.to_string(),
+            game: "steam:252950".to_string(),
+            counts: Counts {
+                triggers: auto_clicks + 1,
+                auto_clicks,
+                natural_double_taps: 0,
+                suppressed: 1,
+                send_failures: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn records_read_back_as_written() {
+        let history = TempHistory::new("round-trip");
+        let written = [record("2026-10-01", 10, "default", 3), record("2026-10-02", 11, "ranked", 5)];
+        for record in &written {
+            append(&history.0, record).unwrap();
+        }
+
+        let read = load(&history.0, i64::MIN, i64::MAX).unwrap();
+        assert_eq!(read.len(), 2);
+        for (read, written) in read.iter().zip(&written) {
+            assert_eq!((read.start, read.end), (written.start, written.end));
+            assert_eq!(read.profile, written.profile);
+            assert_eq!(read.game, written.game);
+            assert_eq!(read.counts, written.counts);
+        }
+    }
+
+    #[test]
+    fn a_missing_file_or_cut_short_line_is_not_an_error() {
+        let history = TempHistory::new("malformed");
+        assert!(load(&history.0, i64::MIN, i64::MAX).unwrap().is_empty());
+
+        append(&history.0, &record("2026-10-01", 10, "default", 3)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&history.0).unwrap();
+        file.write_all(b"{\"start\":17").unwrap();
+        assert_eq!(load(&history.0, i64::MIN, i64::MAX).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn load_keeps_records_starting_in_the_range() {
+        let history = TempHistory::new("range");
+        for day in ["2026-09-30", "2026-10-01", "2026-10-02"] {
+            append(&history.0, &record(day, 12, "default", 1)).unwrap();
+        }
+
+        let records = load(&history.0, day_start(date("2026-10-01")), day_start(date("2026-10-02"))).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].start, day_start(date("2026-10-01")) + 12 * 3600);
+    }
+
+    #[test]
+    fn summary_groups_by_day_and_profile() {
+        let history = TempHistory::new("summary");
+        for record in [
+            record("2026-09-30", 12, "default", 100),
+            record("2026-10-01", 9, "default", 2),
+            record("2026-10-01", 20, "ranked", 3),
+            record("2026-10-02", 9, "ranked", 4),
+            record("2026-10-03", 9, "ranked", 100),
+        ] {
+            append(&history.0, &record).unwrap();
+        }
+
+        let summary = Summary::load(&history.0, date("2026-10-01"), date("2026-10-02")).unwrap();
+        let auto_clicks = |counts: Option<&Counts>| counts.map(|counts| counts.auto_clicks);
+        assert_eq!(summary.by_day.len(), 2);
+        assert_eq!(auto_clicks(summary.by_day.get(&date("2026-10-01"))), Some(5));
+        assert_eq!(auto_clicks(summary.by_day.get(&date("2026-10-02"))), Some(4));
+        assert_eq!(auto_clicks(summary.by_profile.get("default")), Some(2));
+        assert_eq!(auto_clicks(summary.by_profile.get("ranked")), Some(7));
+        assert_eq!(auto_clicks(summary.by_game.get("steam:252950")), Some(9));
+        assert_eq!(summary.total.auto_clicks, 9);
+        assert_eq!(summary.total.triggers, 12);
+        assert_eq!(summary.total.suppressed, 3);
+    }
+
+    #[test]
+    fn date_range_counts_back_from_the_last_day() {
+        let last = date("2026-10-07");
+        assert_eq!(date_range(7, None, last).unwrap(), (date("2026-10-01"), last));
+        assert_eq!(date_range(1, None, last).unwrap(), (last, last));
+        assert_eq!(date_range(0, None, last).unwrap(), (last, last));
+        assert_eq!(date_range(7, Some(date("2026-09-01")), last).unwrap(), (date("2026-09-01"), last));
+    }
+
+    #[test]
+    fn huge_day_counts_are_an_error() {
+        let error = date_range(u32::MAX, None, date("2026-10-07")).unwrap_err();
+        assert!(matches!(error, DoubleTapError::History(_)));
+        assert!(error.to_string().contains("4294967295 days before 2026-10-07 is out of range"));
+    }
+}
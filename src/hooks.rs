@@ -0,0 +1,120 @@
+//! User hooks: shell commands run on lifecycle and state events
+//!
+//! Commands come from the `[hooks]` config section and run through `sh -c`
+//! with the event details in `DOUBLETAP_*` environment variables. They are
+//! spawned from a dedicated thread and never waited on by the main loop.
+
+use serde::Deserialize;
+use std::process::{Child, Command};
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::events::{DaemonEvent, EventBus};
+
+/// Hook commands (`[hooks]` in the config file)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HookConfig {
+    /// Daemon started and the virtual device is ready
+    pub start: Option<String>,
+    /// Daemon is shutting down
+    pub stop: Option<String>,
+    /// Game window gained focus
+    pub focus_gained: Option<String>,
+    /// Game window lost focus
+    pub focus_lost: Option<String>,
+    /// Auto-clicking was enabled
+    pub enabled: Option<String>,
+    /// Auto-clicking was disabled
+    pub disabled: Option<String>,
+    /// An auto-click could not be sent
+    pub click_failed: Option<String>,
+}
+
+impl HookConfig {
+    /// Whether any hook is configured
+    pub fn is_empty(&self) -> bool {
+        [
+            &self.start,
+            &self.stop,
+            &self.focus_gained,
+            &self.focus_lost,
+            &self.enabled,
+            &self.disabled,
+            &self.click_failed,
+        ]
+        .iter()
+        .all(|hook| hook.is_none())
+    }
+
+    /// Command configured for an event
+    fn command_for(&self, event: &DaemonEvent) -> Option<&str> {
+        let hook = match event {
+            DaemonEvent::Started => &self.start,
+            DaemonEvent::Stopped => &self.stop,
+            DaemonEvent::Focus(true) => &self.focus_gained,
+            DaemonEvent::Focus(false) => &self.focus_lost,
+            DaemonEvent::Enabled(true) => &self.enabled,
+            DaemonEvent::Enabled(false) => &self.disabled,
+            DaemonEvent::DeviceError(_) => &self.click_failed,
+        };
+        hook.as_deref()
+    }
+}
+
+/// Spawn a hook command with the event details in its environment
+fn spawn_hook(command: &str, event: &DaemonEvent) -> std::io::Result<Child> {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .env("DOUBLETAP_EVENT", event.name())
+        .env("DOUBLETAP_MESSAGE", event.describe())
+        .env("DOUBLETAP_PID", std::process::id().to_string());
+    if let DaemonEvent::DeviceError(error) = event {
+        cmd.env("DOUBLETAP_ERROR", error);
+    }
+    cmd.spawn()
+}
+
+/// Start a background thread that runs hooks for daemon events
+///
+/// The thread exits once every [`EventBus`] handle has been dropped, after
+/// spawning hooks for any events still queued (so the `stop` hook runs).
+pub fn start_hook_runner(config: HookConfig, events: &EventBus) -> thread::JoinHandle<()> {
+    let receiver = events.subscribe();
+    info!("User hooks enabled");
+
+    thread::spawn(move || {
+        let mut children: Vec<Child> = Vec::new();
+
+        loop {
+            match receiver.recv_timeout(Duration::from_millis(200)) {
+                Ok(event) => {
+                    if let Some(command) = config.command_for(&event) {
+                        debug!("Running {} hook: {}", event.name(), command);
+                        match spawn_hook(command, &event) {
+                            Ok(child) => children.push(child),
+                            Err(e) => warn!("Failed to run {} hook: {}", event.name(), e),
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            // Reap finished hooks so they don't linger as zombies
+            children.retain_mut(|child| match child.try_wait() {
+                Ok(Some(status)) => {
+                    if !status.success() {
+                        debug!("Hook exited with {}", status);
+                    }
+                    false
+                }
+                Ok(None) => true,
+                Err(_) => false,
+            });
+        }
+    })
+}
@@ -0,0 +1,231 @@
+//! Hotkey chords such as `Ctrl+Alt+D`
+//!
+//! Parsed from the config file and matched by the input listener against
+//! the keys it sees, so no extra keyboard grab is needed.
+
+use rdev::Key;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Modifier keys, matching either the left or right physical key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Super,
+}
+
+impl Modifier {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ctrl" | "control" => Some(Self::Ctrl),
+            "shift" => Some(Self::Shift),
+            "alt" => Some(Self::Alt),
+            "super" | "meta" | "win" => Some(Self::Super),
+            _ => None,
+        }
+    }
+
+    fn keys(self) -> [Key; 2] {
+        match self {
+            Self::Ctrl => [Key::ControlLeft, Key::ControlRight],
+            Self::Shift => [Key::ShiftLeft, Key::ShiftRight],
+            Self::Alt => [Key::Alt, Key::AltGr],
+            Self::Super => [Key::MetaLeft, Key::MetaRight],
+        }
+    }
+}
+
+/// Parse a (lowercase) key name into an rdev key
+fn key_from_name(name: &str) -> Option<Key> {
+    let key = match name {
+        "a" => Key::KeyA, "b" => Key::KeyB, "c" => Key::KeyC, "d" => Key::KeyD,
+        "e" => Key::KeyE, "f" => Key::KeyF, "g" => Key::KeyG, "h" => Key::KeyH,
+        "i" => Key::KeyI, "j" => Key::KeyJ, "k" => Key::KeyK, "l" => Key::KeyL,
+        "m" => Key::KeyM, "n" => Key::KeyN, "o" => Key::KeyO, "p" => Key::KeyP,
+        "q" => Key::KeyQ, "r" => Key::KeyR, "s" => Key::KeyS, "t" => Key::KeyT,
+        "u" => Key::KeyU, "v" => Key::KeyV, "w" => Key::KeyW, "x" => Key::KeyX,
+        "y" => Key::KeyY, "z" => Key::KeyZ,
+        "0" => Key::Num0, "1" => Key::Num1, "2" => Key::Num2, "3" => Key::Num3,
+        "4" => Key::Num4, "5" => Key::Num5, "6" => Key::Num6, "7" => Key::Num7,
+        "8" => Key::Num8, "9" => Key::Num9,
+        "f1" => Key::F1, "f2" => Key::F2, "f3" => Key::F3, "f4" => Key::F4,
+        "f5" => Key::F5, "f6" => Key::F6, "f7" => Key::F7, "f8" => Key::F8,
+        "f9" => Key::F9, "f10" => Key::F10, "f11" => Key::F11, "f12" => Key::F12,
+        "space" => Key::Space, "tab" => Key::Tab, "enter" | "return" => Key::Return,
+        "escape" | "esc" => Key::Escape, "backspace" => Key::Backspace,
+        "insert" => Key::Insert, "delete" => Key::Delete, "home" => Key::Home,
+        "end" => Key::End, "pageup" => Key::PageUp, "pagedown" => Key::PageDown,
+        "up" => Key::UpArrow, "down" => Key::DownArrow, "left" => Key::LeftArrow,
+        "right" => Key::RightArrow, "pause" => Key::Pause, "scrolllock" => Key::ScrollLock,
+        "printscreen" => Key::PrintScreen, "grave" | "`" => Key::BackQuote,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// A key chord: any number of modifiers plus one final key
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Hotkey {
+    modifiers: Vec<Modifier>,
+    key: Key,
+    name: String,
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<String> = s.split('+').map(|part| part.trim().to_lowercase()).collect();
+        let (key_name, modifier_names) = parts
+            .split_last()
+            .filter(|(key, _)| !key.is_empty())
+            .ok_or_else(|| format!("empty hotkey '{}'", s))?;
+
+        let modifiers = modifier_names
+            .iter()
+            .map(|name| {
+                Modifier::from_name(name)
+                    .ok_or_else(|| format!("unknown modifier '{}' in hotkey '{}'", name, s))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let key = key_from_name(key_name)
+            .ok_or_else(|| format!("unknown key '{}' in hotkey '{}'", key_name, s))?;
+
+        Ok(Self {
+            modifiers,
+            key,
+            name: s.trim().to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for Hotkey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// Tracks held keys and reports when a hotkey chord is pressed
+pub struct HotkeyTracker {
+    hotkey: Hotkey,
+    held: HashSet<Key>,
+}
+
+impl HotkeyTracker {
+    pub fn new(hotkey: Hotkey) -> Self {
+        Self {
+            hotkey,
+            held: HashSet::new(),
+        }
+    }
+
+    /// Record a key press, returning true if it completes the chord
+    pub fn on_press(&mut self, key: Key) -> bool {
+        // Auto-repeat re-sends presses for held keys; only fire once
+        let newly_pressed = self.held.insert(key);
+        newly_pressed
+            && key == self.hotkey.key
+            && self
+                .hotkey
+                .modifiers
+                .iter()
+                .all(|modifier| modifier.keys().iter().any(|k| self.held.contains(k)))
+    }
+
+    /// Record a key release
+    pub fn on_release(&mut self, key: Key) {
+        self.held.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hotkey(s: &str) -> Hotkey {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_chords() {
+        let chord = hotkey("Ctrl+Alt+D");
+        assert_eq!(chord.modifiers, [Modifier::Ctrl, Modifier::Alt]);
+        assert_eq!(chord.key, Key::KeyD);
+        assert_eq!(chord.to_string(), "Ctrl+Alt+D");
+
+        let chord = hotkey(" control + SHIFT + f12 ");
+        assert_eq!(chord.modifiers, [Modifier::Ctrl, Modifier::Shift]);
+        assert_eq!(chord.key, Key::F12);
+        assert_eq!(chord.to_string(), "control + SHIFT + f12");
+
+        assert_eq!(hotkey("Meta+`").modifiers, [Modifier::Super]);
+        assert_eq!(hotkey("Win+Space").key, Key::Space);
+        assert!(hotkey("Pause").modifiers.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_chords() {
+        for (s, error) in [
+            ("", "empty hotkey ''"),
+            ("Ctrl+", "empty hotkey 'Ctrl+'"),
+            ("Hyper+D", "unknown modifier 'hyper' in hotkey 'Hyper+D'"),
+            ("Ctrl+Foo", "unknown key 'foo' in hotkey 'Ctrl+Foo'"),
+            ("Ctrl+Alt", "unknown key 'alt' in hotkey 'Ctrl+Alt'"),
+            ("D+Ctrl", "unknown modifier 'd' in hotkey 'D+Ctrl'"),
+        ] {
+            assert_eq!(s.parse::<Hotkey>(), Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn fires_once_the_chord_is_complete() {
+        let mut tracker = HotkeyTracker::new(hotkey("Ctrl+Alt+D"));
+        assert!(!tracker.on_press(Key::ControlLeft));
+        assert!(!tracker.on_press(Key::Alt));
+        assert!(tracker.on_press(Key::KeyD));
+    }
+
+    #[test]
+    fn auto_repeat_does_not_fire_again() {
+        let mut tracker = HotkeyTracker::new(hotkey("Ctrl+D"));
+        tracker.on_press(Key::ControlRight);
+        assert!(tracker.on_press(Key::KeyD));
+        assert!(!tracker.on_press(Key::KeyD));
+
+        tracker.on_release(Key::KeyD);
+        assert!(tracker.on_press(Key::KeyD));
+    }
+
+    #[test]
+    fn needs_every_modifier_held() {
+        let mut tracker = HotkeyTracker::new(hotkey("Ctrl+Shift+D"));
+        tracker.on_press(Key::ControlLeft);
+        assert!(!tracker.on_press(Key::KeyD));
+        tracker.on_release(Key::KeyD);
+
+        tracker.on_press(Key::ShiftLeft);
+        tracker.on_release(Key::ControlLeft);
+        assert!(!tracker.on_press(Key::KeyD));
+    }
+
+    #[test]
+    fn key_held_before_the_modifiers_does_not_fire() {
+        let mut tracker = HotkeyTracker::new(hotkey("Ctrl+D"));
+        tracker.on_press(Key::KeyD);
+        tracker.on_press(Key::ControlLeft);
+        assert!(!tracker.on_press(Key::KeyD));
+    }
+}
@@ -1,111 +1,515 @@
-//! Global input listening using rdev
+//! Global input listening
+//!
+//! Trigger releases and the toggle hotkey come from rdev. With device
+//! annotations on (for the event log), the mouse devices in `/dev/input`
+//! are also read through evdev, only to tag each release with the device
+//! it came from and its kernel timestamp. For replays, triggers come
+//! from an evemu recording instead.
 
+use evdev::{Device, InputEventKind, Key};
 use rdev::{listen, Button, Event, EventType};
-use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::evemu::Recording;
+use crate::hotkey::{Hotkey, HotkeyTracker};
+use crate::input_simulator::DEVICE_NAME;
+use crate::DoubleTapError;
+use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc;
-use std::sync::OnceLock;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
-use tracing::error;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{error, info, warn};
+
+/// Event sent when right-click is detected
+#[derive(Debug, Clone)]
+pub struct RightClickEvent {
+    /// Timestamp when the click was detected
+    pub timestamp: std::time::Instant,
+    /// Device the release came from (device annotations only)
+    pub device: Option<String>,
+    /// Kernel timestamp of the release (device annotations only)
+    pub kernel_time: Option<SystemTime>,
+}
+
+/// Events produced by the input listener
+#[derive(Debug, Clone)]
+pub enum ListenerEvent {
+    /// The trigger button was released
+    RightClick(RightClickEvent),
+    /// The toggle hotkey chord was pressed
+    ToggleHotkey,
+}
 
-/// Minimum time between auto-clicks in milliseconds
-/// This prevents feedback loops from our own simulated events
-const MIN_CLICK_INTERVAL_MS: u64 = 100;
+/// How often blocked readers check whether they should stop
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-/// Timestamp of last auto-click we triggered (in millis since program start)
-static LAST_AUTO_CLICK_MS: AtomicU64 = AtomicU64::new(0);
+/// How often `/dev/input` is rescanned for mice that (re)appeared
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
 
-/// Program start time - initialized once, thread-safe
-static PROGRAM_START: OnceLock<Instant> = OnceLock::new();
+/// An evdev release older than this when rdev reports one belongs to an
+/// earlier click
+const ANNOTATION_WINDOW: Duration = Duration::from_millis(50);
 
-/// Get current time in millis since program start
-fn now_ms() -> u64 {
-    let start = PROGRAM_START.get_or_init(Instant::now);
-    start.elapsed().as_millis() as u64
+/// The latest trigger release an evdev reader saw
+struct Annotation {
+    device: String,
+    kernel_time: SystemTime,
+    seen: Instant,
 }
 
-/// Mark that we just sent an auto-click
-/// Call this AFTER sending the auto-click successfully
-pub fn mark_auto_click_sent() {
-    let now = now_ms();
-    LAST_AUTO_CLICK_MS.store(now, Ordering::SeqCst);
+/// Where evdev readers leave releases for rdev to pick up
+type AnnotationSlot = Arc<Mutex<Option<Annotation>>>;
+
+/// Take the release an evdev reader saw for the one rdev just reported,
+/// if any
+fn take_annotation(slot: &AnnotationSlot) -> (Option<String>, Option<SystemTime>) {
+    match slot.lock().unwrap().take() {
+        Some(annotation) if annotation.seen.elapsed() < ANNOTATION_WINDOW => {
+            (Some(annotation.device), Some(annotation.kernel_time))
+        }
+        _ => (None, None),
+    }
 }
 
-/// Check if enough time has passed since last auto-click
-/// Returns true if this event should be ignored (too soon after our auto-click)
-fn should_ignore_event() -> bool {
-    let last_click = LAST_AUTO_CLICK_MS.load(Ordering::SeqCst);
-    
-    // If we've never sent an auto-click, don't ignore
-    if last_click == 0 {
-        return false;
+/// Turns trigger releases into [`ListenerEvent`]s
+struct TriggerHandler {
+    sender: mpsc::Sender<ListenerEvent>,
+}
+
+impl TriggerHandler {
+    fn on_release(&self, device: Option<String>, kernel_time: Option<SystemTime>) {
+        let click_event = RightClickEvent {
+            timestamp: Instant::now(),
+            device,
+            kernel_time,
+        };
+        if let Err(e) = self.sender.send(ListenerEvent::RightClick(click_event)) {
+            error!("Failed to send click event: {}", e);
+        }
     }
-    
-    let now = now_ms();
-    let elapsed = now.saturating_sub(last_click);
-    
-    if elapsed < MIN_CLICK_INTERVAL_MS {
-        return true;
+
+    fn on_hotkey(&self) {
+        if let Err(e) = self.sender.send(ListenerEvent::ToggleHotkey) {
+            error!("Failed to send hotkey event: {}", e);
+        }
     }
-    
-    false
 }
 
-/// Event sent when right-click is detected
-#[derive(Debug, Clone)]
-pub struct RightClickEvent {
-    /// Timestamp when the click was detected
-    pub timestamp: std::time::Instant,
+/// One running listener's share of the rdev thread
+struct RdevRoute {
+    id: u64,
+    handler: TriggerHandler,
+    hotkey: Option<HotkeyTracker>,
+    /// Where this listener's evdev readers leave releases, if it has any
+    annotations: Option<AnnotationSlot>,
+}
+
+impl RdevRoute {
+    fn on_event(&mut self, event: &Event) {
+        match event.event_type {
+            // Trigger on button RELEASE - this ensures user's button is up
+            // before we send our simulated click (avoids button state conflict)
+            EventType::ButtonRelease(Button::Right) => {
+                let (device, kernel_time) = self.annotations.as_ref().map_or((None, None), take_annotation);
+                self.handler.on_release(device, kernel_time);
+            }
+            EventType::KeyPress(key) => {
+                if let Some(tracker) = self.hotkey.as_mut() {
+                    if tracker.on_press(key) {
+                        self.handler.on_hotkey();
+                    }
+                }
+            }
+            EventType::KeyRelease(key) => {
+                if let Some(tracker) = self.hotkey.as_mut() {
+                    tracker.on_release(key);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The process's rdev thread
+///
+/// `rdev::listen` can't be interrupted, so rather than a thread per
+/// listener start, one thread serves every running listener through its
+/// route, and is only replaced once it has died.
+struct RdevThread {
+    routes: Arc<Mutex<Vec<RdevRoute>>>,
+    done: Arc<AtomicBool>,
+}
+
+static RDEV: Mutex<Option<RdevThread>> = Mutex::new(None);
+static NEXT_ROUTE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Deliver rdev events to `route`, starting the rdev thread unless one is
+/// alive; returns the flag set when that thread dies
+fn add_rdev_route(route: RdevRoute) -> Arc<AtomicBool> {
+    let mut rdev = RDEV.lock().unwrap();
+    if rdev
+        .as_ref()
+        .is_none_or(|thread| thread.done.load(Ordering::SeqCst))
+    {
+        *rdev = Some(spawn_rdev());
+    }
+    let thread = rdev.as_ref().unwrap();
+    thread.routes.lock().unwrap().push(route);
+    thread.done.clone()
+}
+
+fn remove_rdev_route(id: u64) {
+    if let Some(thread) = RDEV.lock().unwrap().as_ref() {
+        thread.routes.lock().unwrap().retain(|route| route.id != id);
+    }
+}
+
+fn spawn_rdev() -> RdevThread {
+    let routes: Arc<Mutex<Vec<RdevRoute>>> = Arc::default();
+    let done = Arc::new(AtomicBool::new(false));
+    {
+        let routes = routes.clone();
+        let done = SetOnDrop(done.clone());
+        thread::spawn(move || {
+            let _done = done;
+            let callback = move |event: Event| {
+                for route in routes.lock().unwrap().iter_mut() {
+                    route.on_event(&event);
+                }
+            };
+            if let Err(e) = listen(callback) {
+                error!("Error in input listener: {:?}\nMake sure you have permission to read input devices (add user to 'input' group)", e);
+            }
+        });
+    }
+    RdevThread { routes, done }
+}
+
+/// Identity of a device node; a replugged device gets a new inode, and a
+/// permission change a new ctime
+type NodeId = (u64, i64);
+
+/// Readable mice in `/dev/input` that aren't being read yet, excluding
+/// our own virtual device
+///
+/// Nodes that can't be opened or aren't mice are remembered in `rejected`
+/// and not opened again until the node changes.
+fn new_trigger_devices(
+    reading: &HashMap<PathBuf, thread::JoinHandle<()>>,
+    rejected: &mut HashMap<PathBuf, NodeId>,
+) -> Vec<(PathBuf, Device)> {
+    let Ok(entries) = std::fs::read_dir("/dev/input") else {
+        return Vec::new();
+    };
+    let mut devices = Vec::new();
+    let mut present = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let is_event_node = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with("event"));
+        if !is_event_node || reading.contains_key(&path) {
+            continue;
+        }
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            continue;
+        };
+        let node = (metadata.ino(), metadata.ctime());
+        present.push(path.clone());
+        if rejected.get(&path) == Some(&node) {
+            continue;
+        }
+
+        let mouse = Device::open(&path).ok().filter(|device| {
+            device.name() != Some(DEVICE_NAME)
+                && device
+                    .supported_keys()
+                    .is_some_and(|keys| keys.contains(Key::BTN_RIGHT))
+        });
+        match mouse {
+            Some(device) => {
+                rejected.remove(&path);
+                devices.push((path, device));
+            }
+            None => {
+                rejected.insert(path, node);
+            }
+        }
+    }
+    rejected.retain(|path, _| present.contains(path));
+    devices
+}
+
+/// Record trigger releases from one evdev device in `slot` until it goes
+/// away or `active` is cleared
+fn watch_device(path: PathBuf, mut device: Device, slot: AnnotationSlot, active: Arc<AtomicBool>) {
+    let label = format!("{} ({})", path.display(), device.name().unwrap_or("unnamed"));
+    // Non-blocking, so the wait below can time out and check `active`
+    let fd = device.as_raw_fd();
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+    }
+
+    while active.load(Ordering::SeqCst) {
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ready = unsafe { libc::poll(&mut pollfd, 1, STOP_POLL_INTERVAL.as_millis() as i32) };
+        if ready <= 0 {
+            continue;
+        }
+
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+            Err(e) => {
+                warn!("Stopped reading {}: {}", label, e);
+                return;
+            }
+        };
+        for event in events {
+            if event.kind() == InputEventKind::Key(Key::BTN_RIGHT) && event.value() == 0 {
+                *slot.lock().unwrap() = Some(Annotation {
+                    device: label.clone(),
+                    kernel_time: event.timestamp(),
+                    seen: Instant::now(),
+                });
+            }
+        }
+    }
+}
+
+/// Whether a trigger release from the virtual device at `node` comes back
+/// through the evdev reader used for annotations, after `send` runs; a
+/// uinput loopback for `doctor`
+///
+/// This reads below rdev and X11, so it says nothing about whether the
+/// listener itself would see the click. The annotator skips virtual
+/// devices, so `node` is opened here directly. It must be the caller's own
+/// device: a running daemon's has the same name.
+pub fn echo_trigger(
+    node: &Path,
+    send: impl FnOnce(),
+    timeout: Duration,
+) -> Result<Option<RightClickEvent>, DoubleTapError> {
+    let path = node.to_path_buf();
+    let device = Device::open(&path)
+        .map_err(|e| DoubleTapError::InputAccess(format!("{} is not readable: {}", path.display(), e)))?;
+    let slot = AnnotationSlot::default();
+    let active = Arc::new(AtomicBool::new(true));
+    let reader = {
+        let slot = slot.clone();
+        let active = active.clone();
+        thread::spawn(move || watch_device(path, device, slot, active))
+    };
+
+    send();
+    let start = Instant::now();
+    let mut echo = None;
+    while echo.is_none() && start.elapsed() < timeout {
+        echo = slot.lock().unwrap().take().map(|annotation| RightClickEvent {
+            timestamp: annotation.seen,
+            device: Some(annotation.device),
+            kernel_time: Some(annotation.kernel_time),
+        });
+        thread::sleep(Duration::from_millis(5));
+    }
+    active.store(false, Ordering::SeqCst);
+    let _ = reader.join();
+    Ok(echo)
+}
+
+/// Keep one evdev reader per mouse in `/dev/input` until `active` is
+/// cleared, rescanning so a mouse that drops out and comes back is read
+/// again
+fn annotate_from_devices(slot: AnnotationSlot, active: Arc<AtomicBool>) {
+    let mut readers: HashMap<PathBuf, thread::JoinHandle<()>> = HashMap::new();
+    let mut rejected = HashMap::new();
+    let mut last_scan: Option<Instant> = None;
+    let mut had_readers = None;
+    while active.load(Ordering::SeqCst) {
+        // Readers end when their mouse goes away
+        readers.retain(|_, handle| !handle.is_finished());
+        if last_scan.is_none_or(|at| at.elapsed() >= RESCAN_INTERVAL) {
+            for (path, device) in new_trigger_devices(&readers, &mut rejected) {
+                let slot = slot.clone();
+                let active = active.clone();
+                let reader_path = path.clone();
+                readers.insert(
+                    path,
+                    thread::spawn(move || watch_device(reader_path, device, slot, active)),
+                );
+            }
+            last_scan = Some(Instant::now());
+        }
+
+        let has_readers = !readers.is_empty();
+        if had_readers != Some(has_readers) {
+            if has_readers {
+                info!("Annotating triggers from {} input device(s)", readers.len());
+            } else {
+                warn!("No readable mouse in /dev/input, logging triggers without device or kernel time");
+            }
+            had_readers = Some(has_readers);
+        }
+        thread::sleep(STOP_POLL_INTERVAL);
+    }
+
+    for (_, handle) in readers {
+        let _ = handle.join();
+    }
+}
+
+/// Feed a recording's trigger releases to the handler with their original
+/// timing
+fn replay_recording(recording: Recording, handler: TriggerHandler, running: Arc<AtomicBool>) {
+    info!(
+        "Replaying {} trigger(s) recorded from {}",
+        recording.trigger_count(),
+        recording.name
+    );
+    let label = format!("replay ({})", recording.name);
+    let start = Instant::now();
+    for event in recording.events.iter().filter(|event| event.is_trigger_release()) {
+        while let Some(wait) = event.time.checked_sub(start.elapsed()) {
+            if !running.load(Ordering::SeqCst) {
+                info!("Replay stopped");
+                return;
+            }
+            thread::sleep(wait.min(STOP_POLL_INTERVAL));
+        }
+        handler.on_release(Some(label.clone()), None);
+    }
+    info!("Replay finished");
 }
 
 /// Input listener that captures global mouse events
+#[derive(Clone)]
 pub struct InputListener {
-    /// Sender for click events
-    sender: mpsc::Sender<RightClickEvent>,
+    /// Sender for listener events
+    sender: mpsc::Sender<ListenerEvent>,
+    /// Optional chord that toggles auto-clicking
+    toggle_hotkey: Option<Hotkey>,
+    /// Tag releases with their device and kernel timestamp
+    annotate: bool,
+    /// Recording to replay instead of listening to real devices
+    replay: Option<Recording>,
+    /// Cleared to stop the listener threads
+    running: Arc<AtomicBool>,
 }
 
 impl InputListener {
     /// Create a new InputListener with the given channel sender
-    pub fn new(sender: mpsc::Sender<RightClickEvent>) -> Self {
-        Self { sender }
+    pub fn new(sender: mpsc::Sender<ListenerEvent>) -> Self {
+        Self {
+            sender,
+            toggle_hotkey: None,
+            annotate: false,
+            replay: None,
+            running: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Also watch the keyboard for a chord that toggles auto-clicking
+    pub fn with_toggle_hotkey(mut self, hotkey: Hotkey) -> Self {
+        self.toggle_hotkey = Some(hotkey);
+        self
+    }
+
+    /// Also read the mice in `/dev/input`, to tag each release with its
+    /// device and kernel timestamp; the trigger itself still comes from rdev
+    pub fn with_device_annotations(mut self) -> Self {
+        self.annotate = true;
+        self
+    }
+
+    /// Take trigger releases from a recording instead of the real devices
+    pub fn replaying(mut self, recording: Recording) -> Self {
+        self.replay = Some(recording);
+        self
     }
 
-    /// Start listening for input events in a background thread
+    /// Stop listening once `running` is cleared
+    pub fn with_running(mut self, running: Arc<AtomicBool>) -> Self {
+        self.running = running;
+        self
+    }
+
+    /// Start listening for input events in background threads
+    ///
+    /// rdev supplies the trigger and the toggle hotkey. With
+    /// [`with_device_annotations`], evdev readers run alongside and a
+    /// release they saw within the last 50ms is attached to the one rdev
+    /// reports. Every right-click release is sent through the channel;
+    /// filtering our own echoes is up to the
+    /// [`DecisionEngine`](crate::engine::DecisionEngine).
     ///
-    /// This function spawns a new thread that listens for global mouse events.
-    /// When a right-click press is detected, it sends an event through the channel.
+    /// Returns a JoinHandle that finishes once `running` is cleared, or once
+    /// rdev gave up; its device readers are stopped and joined first, so
+    /// `start` can be called again on a clone. `rdev::listen` can't be
+    /// interrupted, so its thread is shared by every listener in the
+    /// process and outlives them, idle, until the process exits.
     ///
-    /// Returns a JoinHandle for the listener thread.
+    /// [`with_device_annotations`]: InputListener::with_device_annotations
     pub fn start(self) -> thread::JoinHandle<()> {
-        thread::spawn(move || {
-            let sender = self.sender;
+        let handler = TriggerHandler {
+            sender: self.sender.clone(),
+        };
 
-            let callback = move |event: Event| {
-                // Trigger on button RELEASE - this ensures user's button is up
-                // before we send our simulated click (avoids button state conflict)
-                if let EventType::ButtonRelease(Button::Right) = event.event_type {
-                    // Check if this might be our own auto-click event
-                    if should_ignore_event() {
-                        return;
-                    }
-                    let click_event = RightClickEvent {
-                        timestamp: std::time::Instant::now(),
-                    };
+        if let Some(recording) = self.replay {
+            let running = self.running;
+            return thread::spawn(move || replay_recording(recording, handler, running));
+        }
 
-                    if let Err(e) = sender.send(click_event) {
-                        error!("Failed to send click event: {}", e);
-                    }
-                }
-            };
+        let slot = self.annotate.then(AnnotationSlot::default);
+        let id = NEXT_ROUTE_ID.fetch_add(1, Ordering::SeqCst);
+        let rdev_done = add_rdev_route(RdevRoute {
+            id,
+            handler,
+            hotkey: self.toggle_hotkey.map(HotkeyTracker::new),
+            annotations: slot.clone(),
+        });
 
-            if let Err(e) = listen(callback) {
-                error!("Error in input listener: {:?}\nMake sure you have permission to read input devices (add user to 'input' group)", e);
+        // Cleared when this listener stops, for whatever reason
+        let active = Arc::new(AtomicBool::new(true));
+        let annotator = slot.map(|slot| {
+            let active = active.clone();
+            thread::spawn(move || annotate_from_devices(slot, active))
+        });
+
+        let running = self.running;
+        thread::spawn(move || {
+            while running.load(Ordering::SeqCst) && !rdev_done.load(Ordering::SeqCst) {
+                thread::sleep(STOP_POLL_INTERVAL);
+            }
+            remove_rdev_route(id);
+            active.store(false, Ordering::SeqCst);
+            if let Some(handle) = annotator {
+                let _ = handle.join();
+            }
+            if !running.load(Ordering::SeqCst) && !rdev_done.load(Ordering::SeqCst) {
+                info!("Leaving the rdev thread behind; it can't be interrupted and stays idle until exit");
             }
         })
     }
 }
 
+/// Sets the flag when dropped, even while unwinding from a panic
+struct SetOnDrop(Arc<AtomicBool>);
+
+impl Drop for SetOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
 /// Create a channel for input events and return both ends
-pub fn create_event_channel() -> (mpsc::Sender<RightClickEvent>, mpsc::Receiver<RightClickEvent>) {
+pub fn create_event_channel() -> (mpsc::Sender<ListenerEvent>, mpsc::Receiver<ListenerEvent>) {
     mpsc::channel()
 }
@@ -7,8 +7,10 @@ use evdev::{
     uinput::VirtualDeviceBuilder, AttributeSet, BusType, InputId, Key, RelativeAxisType,
 };
 use std::os::unix::io::AsRawFd;
-use tracing::{debug, info};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
 
+use crate::output::{OutputSink, EV_KEY, EV_SYN, RIGHT_CLICK, SYN_REPORT};
 use crate::DoubleTapError;
 
 /// Raw input_event struct matching the kernel's struct input_event
@@ -21,10 +23,8 @@ struct RawInputEvent {
     value: i32,
 }
 
-const EV_SYN: u16 = 0x00;
-const EV_KEY: u16 = 0x01;
-const SYN_REPORT: u16 = 0x00;
-const BTN_RIGHT: u16 = 0x111;
+/// Name of the virtual device, so the listener can skip it
+pub const DEVICE_NAME: &str = "DoubleTap-RL Virtual Mouse";
 
 /// All keys registered by ydotool — required for the compositor to
 /// properly recognize and forward events from this device.
@@ -64,6 +64,10 @@ const DEVICE_KEYS: &[Key] = &[
 /// Input simulator using raw writes to uinput fd
 pub struct InputSimulator {
     fd: std::os::unix::io::RawFd,
+    /// Keys and buttons pressed but not yet released
+    held: Vec<u16>,
+    /// The device's event node, once the kernel has created it
+    node: Option<PathBuf>,
     _device: evdev::uinput::VirtualDevice,
 }
 
@@ -85,7 +89,7 @@ impl InputSimulator {
 
         let mut device = VirtualDeviceBuilder::new()
             .map_err(|e| DoubleTapError::VirtualDevice(format!("Builder: {e}")))?
-            .name("DoubleTap-RL Virtual Mouse")
+            .name(DEVICE_NAME)
             .input_id(id)
             .with_keys(&keys)
             .map_err(|e| DoubleTapError::VirtualDevice(format!("Keys: {e}")))?
@@ -102,11 +106,12 @@ impl InputSimulator {
 
         let fd = device.as_raw_fd();
 
-        if let Some(Ok(path)) = device
+        let node = device
             .enumerate_dev_nodes_blocking()
             .ok()
             .and_then(|mut iter| iter.next())
-        {
+            .and_then(Result::ok);
+        if let Some(path) = &node {
             info!("Virtual device: {:?}", path);
         }
 
@@ -114,10 +119,20 @@ impl InputSimulator {
         std::thread::sleep(std::time::Duration::from_secs(1));
         info!("Virtual input device ready");
 
-        Ok(Self { fd, _device: device })
+        Ok(Self {
+            fd,
+            held: Vec::new(),
+            node,
+            _device: device,
+        })
     }
 
-    fn write_event(&self, event_type: u16, code: u16, value: i32) -> Result<(), DoubleTapError> {
+    /// Event node of this virtual device, such as `/dev/input/event21`
+    pub fn dev_node(&self) -> Option<&Path> {
+        self.node.as_deref()
+    }
+
+    pub(crate) fn write_event(&mut self, event_type: u16, code: u16, value: i32) -> Result<(), DoubleTapError> {
         let event = RawInputEvent {
             tv_sec: 0,
             tv_usec: 0,
@@ -133,15 +148,39 @@ impl InputSimulator {
                 std::io::Error::last_os_error().to_string(),
             ));
         }
+        if event_type == EV_KEY {
+            self.held.retain(|held| *held != code);
+            if value != 0 {
+                self.held.push(code);
+            }
+        }
         Ok(())
     }
 
+    /// Release anything a failed write left pressed
+    pub fn release_all(&mut self) -> Result<(), DoubleTapError> {
+        for code in std::mem::take(&mut self.held) {
+            debug!("Releasing held key {:#05x}", code);
+            self.write_event(EV_KEY, code, 0)?;
+        }
+        self.write_event(EV_SYN, SYN_REPORT, 0)
+    }
+
     /// Send a right-click (press + sync + release + sync)
     pub fn send_right_click(&mut self) -> Result<(), DoubleTapError> {
-        self.write_event(EV_KEY, BTN_RIGHT, 1)?;
-        self.write_event(EV_SYN, SYN_REPORT, 0)?;
-        self.write_event(EV_KEY, BTN_RIGHT, 0)?;
-        self.write_event(EV_SYN, SYN_REPORT, 0)?;
-        Ok(())
+        self.emit(&RIGHT_CLICK)
+    }
+}
+
+impl Drop for InputSimulator {
+    fn drop(&mut self) {
+        // Don't count on the compositor noticing a button still down on a
+        // device that vanished
+        if !self.held.is_empty() {
+            if let Err(e) = self.release_all() {
+                warn!("Failed to release held buttons: {}", e);
+            }
+        }
+        info!("Removing virtual input device");
     }
 }
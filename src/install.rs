@@ -0,0 +1,289 @@
+//! Device permissions and the systemd user unit
+//!
+//! Instead of the `input` group, which can read every keyboard, `install`
+//! writes a udev rule tagging `/dev/uinput` and the chosen trigger mice
+//! `uaccess`, so logind grants them to whoever is logged in at the seat,
+//! and a `Type=notify` user unit for the daemon. `uninstall` removes both.
+//!
+//! Mice are found through sysfs, which needs no access to the devices.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{info, warn};
+
+use crate::input_simulator::DEVICE_NAME;
+use crate::DoubleTapError;
+
+/// Where the udev rule goes (needs root)
+pub const UDEV_RULE_PATH: &str = "/etc/udev/rules.d/70-doubletap-rl.rules";
+
+pub const UNIT_NAME: &str = "doubletap-rl.service";
+
+const SYSFS_INPUT: &str = "/sys/class/input";
+
+const BTN_RIGHT: usize = 0x111;
+
+/// A mouse the daemon listens to, identified the way udev sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriggerDevice {
+    pub path: PathBuf,
+    pub name: String,
+    pub vendor: String,
+    pub product: String,
+}
+
+impl TriggerDevice {
+    /// The device behind an event node such as `/dev/input/event5`
+    pub fn from_path(path: &Path) -> Result<Self, DoubleTapError> {
+        let node = path
+            .file_name()
+            .ok_or_else(|| DoubleTapError::Install(format!("{} is not an event node", path.display())))?;
+        read_device(&Path::new(SYSFS_INPUT).join(node)).ok_or_else(|| {
+            DoubleTapError::Install(format!("{} is not a mouse with a right button", path.display()))
+        })
+    }
+}
+
+/// Every mouse with a right button, our own virtual one excepted
+pub fn trigger_devices() -> Vec<TriggerDevice> {
+    let Ok(entries) = fs::read_dir(SYSFS_INPUT) else {
+        return Vec::new();
+    };
+    let mut devices: Vec<TriggerDevice> = entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+        .filter_map(|entry| read_device(&entry.path()))
+        .filter(|device| device.name != DEVICE_NAME)
+        .collect();
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+    devices
+}
+
+/// Read an `eventN` entry of `/sys/class/input`, if it has a right button
+fn read_device(event: &Path) -> Option<TriggerDevice> {
+    let device = event.join("device");
+    let read = |attribute: &str| {
+        fs::read_to_string(device.join(attribute))
+            .ok()
+            .map(|value| value.trim().to_string())
+    };
+    if !has_key(&read("capabilities/key")?, BTN_RIGHT) {
+        return None;
+    }
+    Some(TriggerDevice {
+        path: Path::new("/dev/input").join(event.file_name()?),
+        name: read("name")?,
+        vendor: read("id/vendor")?,
+        product: read("id/product")?,
+    })
+}
+
+/// Whether a sysfs capability bitmap (hex words, most significant first)
+/// has `code` set
+fn has_key(bitmap: &str, code: usize) -> bool {
+    let bits = usize::BITS as usize;
+    bitmap
+        .split_whitespace()
+        .rev()
+        .nth(code / bits)
+        .and_then(|word| usize::from_str_radix(word, 16).ok())
+        .is_some_and(|word| word & (1 << (code % bits)) != 0)
+}
+
+/// udev rule granting the seat user `/dev/uinput` and `devices`
+///
+/// Only the mouse interface of each device is matched: receivers and
+/// gaming mice often share their IDs with a keyboard or macro interface.
+pub fn udev_rule(devices: &[TriggerDevice]) -> String {
+    let mut rule = String::from(
+        "# DoubleTap-RL: virtual mouse and trigger devices for the logged-in user\n\
+         KERNEL==\"uinput\", SUBSYSTEM==\"misc\", OPTIONS+=\"static_node=uinput\", TAG+=\"uaccess\"\n",
+    );
+    for device in devices {
+        rule.push_str(&format!(
+            "# {} ({})\n\
+             SUBSYSTEM==\"input\", KERNEL==\"event*\", ENV{{ID_INPUT_MOUSE}}==\"1\", ATTRS{{id/vendor}}==\"{}\", ATTRS{{id/product}}==\"{}\", TAG+=\"uaccess\"\n",
+            device.name,
+            device.path.display(),
+            device.vendor,
+            device.product
+        ));
+    }
+    rule
+}
+
+/// User unit running `exe` as the daemon
+pub fn systemd_unit(exe: &Path) -> String {
+    format!(
+        "[Unit]\n\
+         Description=DoubleTap-RL auto-clicker\n\
+         After=graphical-session.target\n\
+         PartOf=graphical-session.target\n\
+         \n\
+         [Service]\n\
+         Type=notify\n\
+         ExecStart={}\n\
+         WatchdogSec=10\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=graphical-session.target\n",
+        exe.display()
+    )
+}
+
+/// Where the user unit goes (usually `~/.config/systemd/user`)
+pub fn unit_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("systemd").join("user").join(UNIT_NAME))
+}
+
+/// Write the udev rule and have udev apply it to existing devices
+pub fn install_udev_rule(rule: &str) -> Result<(), DoubleTapError> {
+    fs::write(UDEV_RULE_PATH, rule).map_err(|e| udev_error(e, "install"))?;
+    info!("Wrote {}", UDEV_RULE_PATH);
+    reload_udev();
+    Ok(())
+}
+
+pub fn uninstall_udev_rule() -> Result<(), DoubleTapError> {
+    match fs::remove_file(UDEV_RULE_PATH) {
+        Ok(()) => {
+            info!("Removed {}", UDEV_RULE_PATH);
+            reload_udev();
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(udev_error(e, "uninstall")),
+    }
+    Ok(())
+}
+
+/// Write the user unit and reload the user manager
+pub fn install_unit(unit: &str) -> Result<PathBuf, DoubleTapError> {
+    let path = unit_path().ok_or_else(|| DoubleTapError::Install("HOME is not set".to_string()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| file_error(dir, e))?;
+    }
+    fs::write(&path, unit).map_err(|e| file_error(&path, e))?;
+    info!("Wrote {}", path.display());
+    run("systemctl", &["--user", "daemon-reload"]);
+    Ok(path)
+}
+
+pub fn uninstall_unit() -> Result<(), DoubleTapError> {
+    let path = unit_path().ok_or_else(|| DoubleTapError::Install("HOME is not set".to_string()))?;
+    if path.exists() {
+        run("systemctl", &["--user", "disable", "--now", UNIT_NAME]);
+    }
+    if remove(&path)? {
+        run("systemctl", &["--user", "daemon-reload"]);
+    }
+    Ok(())
+}
+
+/// Remove `path`, returning whether it was there
+fn remove(path: &Path) -> Result<bool, DoubleTapError> {
+    match fs::remove_file(path) {
+        Ok(()) => {
+            info!("Removed {}", path.display());
+            Ok(true)
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(file_error(path, e)),
+    }
+}
+
+fn reload_udev() {
+    run("udevadm", &["control", "--reload-rules"]);
+    run("udevadm", &["trigger", "--subsystem-match=input", "--subsystem-match=misc"]);
+}
+
+/// Run a helper command, warning if it fails
+fn run(program: &str, args: &[&str]) {
+    match Command::new(program).args(args).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("{} {} failed ({})", program, args.join(" "), status),
+        Err(e) => warn!("Could not run {}: {}", program, e),
+    }
+}
+
+fn file_error(path: &Path, error: io::Error) -> DoubleTapError {
+    DoubleTapError::Install(format!("{}: {}", path.display(), error))
+}
+
+/// The rule lives outside the user's reach; say how to retry as root
+fn udev_error(error: io::Error, command: &str) -> DoubleTapError {
+    if error.kind() == io::ErrorKind::PermissionDenied {
+        DoubleTapError::Install(format!(
+            "{}: permission denied, run `sudo doubletap-rl {} --udev` for the udev rule",
+            UDEV_RULE_PATH, command
+        ))
+    } else {
+        file_error(Path::new(UDEV_RULE_PATH), error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn has_key_reads_the_bitmap_from_the_last_word() {
+        // A mouse: BTN_LEFT..BTN_EXTRA in the fifth word from the end
+        let mouse = "1f0000 0 0 0 0";
+        assert!(has_key(mouse, BTN_RIGHT));
+        assert!(has_key(mouse, 0x110));
+        assert!(!has_key(mouse, 0x115));
+        assert!(!has_key(mouse, 30));
+
+        // A keyboard: no buttons at all
+        let keyboard = "120013 0 0 0 0 0 0 0 e080ffdf01cfffff fffffffffffffffe";
+        assert!(has_key(keyboard, 30));
+        assert!(!has_key(keyboard, BTN_RIGHT));
+    }
+
+    #[test]
+    fn has_key_rejects_short_or_garbled_bitmaps() {
+        assert!(!has_key("", BTN_RIGHT));
+        assert!(!has_key("0", BTN_RIGHT));
+        assert!(!has_key("zz 0 0 0 0", BTN_RIGHT));
+    }
+
+    #[test]
+    fn udev_rule_grants_uinput_and_each_mouse_interface() {
+        let devices = [TriggerDevice {
+            path: PathBuf::from("/dev/input/event5"),
+            name: "Logitech G502".to_string(),
+            vendor: "046d".to_string(),
+            product: "c08b".to_string(),
+        }];
+        assert_eq!(
+            udev_rule(&devices),
+            "# DoubleTap-RL: virtual mouse and trigger devices for the logged-in user\n\
+             KERNEL==\"uinput\", SUBSYSTEM==\"misc\", OPTIONS+=\"static_node=uinput\", TAG+=\"uaccess\"\n\
+             # Logitech G502 (/dev/input/event5)\n\
+             SUBSYSTEM==\"input\", KERNEL==\"event*\", ENV{ID_INPUT_MOUSE}==\"1\", ATTRS{id/vendor}==\"046d\", ATTRS{id/product}==\"c08b\", TAG+=\"uaccess\"\n"
+        );
+    }
+
+    #[test]
+    fn udev_rule_without_devices_only_grants_uinput() {
+        let rule = udev_rule(&[]);
+        assert_eq!(rule.lines().filter(|line| !line.starts_with('#')).count(), 1);
+        assert!(rule.contains("KERNEL==\"uinput\""));
+    }
+
+    #[test]
+    fn systemd_unit_is_a_notify_service_with_a_watchdog() {
+        let unit = systemd_unit(Path::new("/usr/local/bin/doubletap-rl"));
+        assert!(unit.contains("\nType=notify\n"));
+        assert!(unit.contains("\nExecStart=/usr/local/bin/doubletap-rl\n"));
+        assert!(unit.contains("\nWatchdogSec=10\n"));
+        assert!(unit.contains("\nRestart=on-failure\n"));
+        assert!(unit.ends_with("[Install]\nWantedBy=graphical-session.target\n"));
+    }
+}
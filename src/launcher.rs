@@ -0,0 +1,211 @@
+//! Steam launch-wrapper support
+//!
+//! `doubletap-rl run -- %command%` spawns the game as a child process, so the
+//! daemon's lifetime follows the game and focus can be matched by PID.
+//!
+//! The game gets a process group of its own. Launchers like Proton
+//! double-fork, leaving the real game process parented to init, but it
+//! keeps the group, so it still counts as part of the game.
+
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, ExitStatus};
+
+use crate::DoubleTapError;
+
+/// A game process spawned by the launch wrapper
+pub struct GameProcess {
+    child: Child,
+}
+
+impl GameProcess {
+    /// Spawn the game command (program followed by its arguments)
+    pub fn spawn(command: &[String]) -> Result<Self, DoubleTapError> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| DoubleTapError::Launch("No game command given".to_string()))?;
+
+        let child = Command::new(program)
+            .args(args)
+            .process_group(0)
+            .spawn()
+            .map_err(|e| DoubleTapError::Launch(format!("{}: {}", program, e)))?;
+
+        Ok(Self { child })
+    }
+
+    /// PID of the spawned process (root of the game's process tree, and
+    /// its process group ID)
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Check whether the game has exited, without blocking
+    pub fn try_wait(&mut self) -> Option<ExitStatus> {
+        self.child.try_wait().ok().flatten()
+    }
+
+    /// Send `signal` to the game's whole process group
+    pub fn signal(&self, signal: libc::c_int) -> Result<(), DoubleTapError> {
+        if unsafe { libc::kill(-(self.pid() as libc::pid_t), signal) } != 0 {
+            return Err(DoubleTapError::Launch(format!(
+                "Failed to signal the game: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Block until the game exits
+    pub fn wait(&mut self) -> Result<ExitStatus, DoubleTapError> {
+        self.child
+            .wait()
+            .map_err(|e| DoubleTapError::Launch(format!("Failed to wait for game: {}", e)))
+    }
+}
+
+/// Exit code for the wrapper, the way a shell reports `status`: the game's
+/// own code, or 128 plus the signal that killed it
+pub fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+/// Where a process sits in the process hierarchy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ProcStat {
+    parent: u32,
+    group: u32,
+    session: u32,
+}
+
+impl ProcStat {
+    /// Read `/proc/<pid>/stat`
+    fn read(pid: u32) -> Option<Self> {
+        Self::parse(&std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+    }
+
+    /// Parse the contents of a `/proc/<pid>/stat` file
+    fn parse(stat: &str) -> Option<Self> {
+        // The command name may contain spaces or parens, so skip past the last ')'
+        let (_, rest) = stat.rsplit_once(')')?;
+        let mut fields = rest.split_whitespace().skip(1);
+        Some(Self {
+            parent: fields.next()?.parse().ok()?,
+            group: fields.next()?.parse().ok()?,
+            session: fields.next()?.parse().ok()?,
+        })
+    }
+}
+
+/// Check whether `pid` is `ancestor`, one of its descendants, or in the
+/// process group or session it leads (which catches descendants that were
+/// reparented after a double fork)
+pub fn is_in_process_tree(pid: u32, ancestor: u32) -> bool {
+    let mut current = pid;
+    while current > 1 {
+        if current == ancestor {
+            return true;
+        }
+        match ProcStat::read(current) {
+            Some(stat) if stat.group == ancestor || stat.session == ancestor => return true,
+            Some(stat) => current = stat.parent,
+            None => return false,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_plain_stat_line() {
+        let stat = "4242 (RocketLeague.e) S 4200 4100 4000 0 -1 4194560 1234 0 0 0";
+        assert_eq!(
+            ProcStat::parse(stat),
+            Some(ProcStat {
+                parent: 4200,
+                group: 4100,
+                session: 4000
+            })
+        );
+    }
+
+    #[test]
+    fn parses_command_names_with_spaces_and_parens() {
+        for comm in ["(Steam Client)", "(wine) (x) )", "(a b) c)", "())"] {
+            let stat = format!("17 {} R 16 15 14 0 -1 0", comm);
+            assert_eq!(
+                ProcStat::parse(&stat),
+                Some(ProcStat {
+                    parent: 16,
+                    group: 15,
+                    session: 14
+                }),
+                "{}",
+                stat
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_stat_lines() {
+        assert_eq!(ProcStat::parse("17 (game"), None);
+        assert_eq!(ProcStat::parse("17 (game) R 16"), None);
+        assert_eq!(ProcStat::parse("17 (game) R x 15 14"), None);
+    }
+
+    #[test]
+    fn finds_this_process_under_its_parent() {
+        let pid = std::process::id();
+        let parent = ProcStat::read(pid).unwrap().parent;
+        assert!(is_in_process_tree(pid, pid));
+        assert!(is_in_process_tree(pid, parent));
+    }
+
+    #[test]
+    fn finds_a_reparented_process_by_its_group() {
+        let mut game = GameProcess::spawn(&[
+            "sh".to_string(),
+            "-c".to_string(),
+            "sleep 5 &".to_string(),
+        ])
+        .unwrap();
+        let root = game.pid();
+        game.wait().unwrap();
+        let orphan = loop {
+            // The backgrounded sleep outlives its shell and is reparented
+            let mut children = std::fs::read_dir("/proc").unwrap().flatten().filter_map(|entry| {
+                let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+                (pid != root && ProcStat::read(pid)?.group == root).then_some(pid)
+            });
+            if let Some(pid) = children.next() {
+                break pid;
+            }
+        };
+
+        assert_ne!(ProcStat::read(orphan).unwrap().parent, root);
+        assert!(is_in_process_tree(orphan, root));
+        unsafe {
+            libc::kill(orphan as i32, libc::SIGKILL);
+        }
+    }
+
+    #[test]
+    fn exit_codes_follow_the_shell_convention() {
+        assert_eq!(exit_code(ExitStatus::from_raw(0)), 0);
+        assert_eq!(exit_code(ExitStatus::from_raw(3 << 8)), 3);
+        assert_eq!(exit_code(ExitStatus::from_raw(libc::SIGKILL)), 137);
+        assert_eq!(exit_code(ExitStatus::from_raw(libc::SIGTERM)), 143);
+    }
+
+    #[test]
+    fn signals_reach_the_whole_group() {
+        let mut game = GameProcess::spawn(&["sh".into(), "-c".into(), "sleep 30 & wait".into()]).unwrap();
+        game.signal(libc::SIGTERM).unwrap();
+        assert_eq!(exit_code(game.wait().unwrap()), 143);
+    }
+}
@@ -1,17 +1,63 @@
 //! DoubleTap-RL - Auto-clicker for Rocket League double-tap aerials
 //!
 //! This library provides components for:
-//! - Global input listening (right-click detection)
-//! - Input simulation (sending synthetic clicks)
+//! - Global input listening (right-click and toggle hotkey detection)
+//! - Deterministic decision engine (feedback filter, focus and gate checks, delay)
+//! - Embeddable engine (builder and handle wiring the above together)
+//! - Ordered, bounded shutdown and supervision of background threads
+//! - Input simulation (sending synthetic clicks through an output sink: uinput, dry-run logging, or in-memory recording)
 //! - Focus detection (window/process-based)
+//! - Optional gates (cursor visibility, game phase from Launch.log)
+//! - User configuration, udev rule and systemd unit installation
+//! - Steam launch-wrapper mode (spawning the game) and systemd notifications
+//! - Control socket, D-Bus service, Unix signals and shared daemon state
+//! - State-change events, desktop notifications, user hooks and tray icon
+//! - Session statistics (decision counters, latency histogram) and history
+//! - JSON-lines log of every trigger decision
+//! - Recording and replaying raw device streams (evemu format)
+//! - Setup diagnostics (`doctor`)
 
+pub mod config;
+pub mod control;
+pub mod cursor_gate;
+pub mod dbus;
+pub mod doctor;
+pub mod engine;
+pub mod evemu;
+pub mod event_log;
+pub mod events;
 pub mod focus_detector;
+pub mod game_state;
+pub mod history;
+pub mod hooks;
+pub mod hotkey;
 pub mod input_listener;
 pub mod input_simulator;
+pub mod install;
+pub mod launcher;
+pub mod notifications;
+pub mod output;
+pub mod pipeline;
+pub mod runtime;
+pub mod shutdown;
+pub mod signals;
+pub mod state;
+pub mod stats;
+pub mod supervisor;
+pub mod systemd;
+pub mod tray;
 
+pub use config::Config;
+pub use cursor_gate::{start_cursor_watcher, CursorState};
+pub use events::{DaemonEvent, EventBus};
 pub use focus_detector::{create_focus_detector, FocusDetector, FocusState, start_focus_poller};
-pub use input_listener::InputListener;
+pub use game_state::{start_game_state_tailer, GamePhase, GameState};
+pub use hotkey::Hotkey;
+pub use input_listener::{InputListener, ListenerEvent};
 pub use input_simulator::InputSimulator;
+pub use launcher::GameProcess;
+pub use runtime::{Engine, EngineHandle};
+pub use state::DaemonState;
 
 use thiserror::Error;
 
@@ -33,6 +79,39 @@ pub enum DoubleTapError {
     #[error("Permission denied - add user to 'input' group")]
     PermissionDenied,
 
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    #[error("Failed to launch game: {0}")]
+    Launch(String),
+
+    #[error("Control socket error: {0}")]
+    Control(String),
+
+    #[error("D-Bus error: {0}")]
+    Dbus(String),
+
+    #[error("Statistics history error: {0}")]
+    History(String),
+
+    #[error("Event log error: {0}")]
+    EventLog(String),
+
+    #[error("Recording error: {0}")]
+    Recording(String),
+
+    #[error("Install error: {0}")]
+    Install(String),
+
+    #[error("Signal handling error: {0}")]
+    Signal(String),
+
+    #[error("Setup problems found: {0}")]
+    Doctor(String),
+
+    #[error("Worker failed: {0}")]
+    Worker(String),
+
     #[error("Channel error: {0}")]
     Channel(String),
 }
@@ -3,89 +3,611 @@
 //! Automatically sends a second right-click after detecting the user's
 //! right-click, helping with double-tap aerial mechanics.
 
+use chrono::{Local, NaiveDate};
+use clap::{Parser, Subcommand};
 use doubletap_rl::{
-    create_focus_detector,
-    input_listener::{create_event_channel, mark_auto_click_sent, InputListener},
-    start_focus_poller, DoubleTapError, FocusState, InputSimulator,
+    control::{self, ControlServer, Request},
+    dbus::DbusService,
+    doctor::{self, Outcome},
+    evemu::{self, Recording},
+    history::{self, HistoryRecorder},
+    hooks::start_hook_runner,
+    install::{self, TriggerDevice},
+    launcher,
+    notifications::start_notifier,
+    output::DryRunSink,
+    runtime::{Target, TriggerSource},
+    shutdown::{Workers, SHUTDOWN_TIMEOUT},
+    signals::{Signal, Signals},
+    systemd::SystemdNotifier,
+    tray::start_tray,
+    Config, DaemonEvent, DaemonState, DoubleTapError, Engine, GameProcess,
 };
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 use tracing::{error, info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
-const TARGET_WINDOW: &str = "Rocket League (64-bit, DX11, Cooked)";
+/// Command-line interface
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Cli {
+    /// Run the full pipeline but only log the auto-clicks; no virtual
+    /// device is created, so no /dev/uinput access is needed
+    #[arg(long)]
+    dry_run: bool,
 
-/// Delay (ms) before auto-click — the compositor needs a brief window to
-/// process the physical release before our click arrives. 10ms is reliable
-/// and still under 1 game frame (16.6ms at 60fps), so it's imperceptible.
-const AUTO_CLICK_DELAY_MS: u64 = 15;
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Launch the game and run alongside it until it exits
+    ///
+    /// Use as a Steam launch option: `doubletap-rl run -- %command%`
+    Run {
+        /// Only log the auto-clicks, as for the daemon
+        #[arg(long)]
+        dry_run: bool,
+        /// Game command line
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Show the running daemon's state
+    Status,
+    /// Resume auto-clicking in the running daemon
+    Enable,
+    /// Pause auto-clicking in the running daemon
+    Disable,
+    /// Change the running daemon's auto-click delay
+    SetDelay {
+        /// Delay in milliseconds
+        delay_ms: u64,
+    },
+    /// Make the running daemon re-read its config file
+    Reload,
+    /// Record a mouse's raw event stream in evemu format (Ctrl+C to stop)
+    Record {
+        /// Output file
+        output: PathBuf,
+        /// Device to record (default: the first mouse found)
+        #[arg(long)]
+        device: Option<PathBuf>,
+    },
+    /// Feed a recording's trigger releases through the decision pipeline
+    ///
+    /// The game counts as focused throughout. Nothing is sent unless
+    /// `--emit` is given.
+    Replay {
+        /// Recording made with `record` or evemu-record
+        file: PathBuf,
+        /// Send the auto-clicks through the virtual device
+        #[arg(long)]
+        emit: bool,
+    },
+    /// Summarise recorded statistics per day, profile and game
+    Stats {
+        /// Show the running daemon's current session instead
+        #[arg(long)]
+        session: bool,
+        /// Number of days to summarise, ending today
+        #[arg(long, default_value_t = 7, conflicts_with = "since")]
+        days: u32,
+        /// First day to summarise (YYYY-MM-DD)
+        #[arg(long, value_parser = history::parse_date)]
+        since: Option<NaiveDate>,
+        /// Last day to summarise (YYYY-MM-DD, default today)
+        #[arg(long, value_parser = history::parse_date)]
+        until: Option<NaiveDate>,
+    },
+    /// Grant device access with a udev rule (instead of the `input` group)
+    /// and add a systemd user unit
+    ///
+    /// The udev rule needs root: `sudo doubletap-rl install --udev`.
+    Install {
+        /// Trigger device to grant, e.g. /dev/input/event5 (repeatable;
+        /// default: every mouse)
+        #[arg(long = "device")]
+        devices: Vec<PathBuf>,
+        /// Only show the files, don't write them
+        #[arg(long)]
+        print: bool,
+        /// Only the udev rule
+        #[arg(long, conflicts_with = "unit")]
+        udev: bool,
+        /// Only the systemd user unit
+        #[arg(long)]
+        unit: bool,
+    },
+    /// Check device access, permissions, focus backends and the target
+    /// window, with a fix for each failure
+    Doctor {
+        /// Also send a right-click through the virtual device and read it
+        /// back from its event node, a uinput loopback (the click reaches
+        /// whatever window has focus)
+        #[arg(long)]
+        round_trip: bool,
+    },
+    /// Remove the udev rule and systemd user unit written by `install`
+    Uninstall {
+        /// Only the udev rule
+        #[arg(long, conflicts_with = "unit")]
+        udev: bool,
+        /// Only the systemd user unit
+        #[arg(long)]
+        unit: bool,
+    },
+}
 
 fn main() -> Result<(), DoubleTapError> {
-    let _subscriber = FmtSubscriber::builder()
+    FmtSubscriber::builder()
         .with_max_level(Level::INFO)
         .with_target(false)
         .compact()
         .init();
 
+    let cli = Cli::parse();
+
+    let request = match cli.command {
+        None => return start_daemon(None, cli.dry_run),
+        Some(Command::Run { dry_run, command }) => return start_daemon(Some(&command), dry_run),
+        Some(Command::Status) => Request::Status,
+        Some(Command::Enable) => Request::Enable,
+        Some(Command::Disable) => Request::Disable,
+        Some(Command::SetDelay { delay_ms }) => Request::SetDelay { delay_ms },
+        Some(Command::Reload) => Request::Reload,
+        Some(Command::Record { output, device }) => return record(device, &output),
+        Some(Command::Replay { file, emit }) => return replay(&file, emit),
+        Some(Command::Stats { session: true, .. }) => Request::Stats,
+        Some(Command::Stats { days, since, until, .. }) => {
+            return show_history(days, since, until);
+        }
+        Some(Command::Install { devices, print, udev, unit }) => {
+            return install(&devices, print, !unit, !udev);
+        }
+        Some(Command::Doctor { round_trip }) => return doctor(round_trip),
+        Some(Command::Uninstall { udev, unit }) => {
+            if !unit {
+                install::uninstall_udev_rule()?;
+            }
+            if !udev {
+                install::uninstall_unit()?;
+            }
+            return Ok(());
+        }
+    };
+    run_client(&request)
+}
+
+/// Client mode: send one request to the running daemon and print its state
+fn run_client(request: &Request) -> Result<(), DoubleTapError> {
+    let response = control::send_request(request)?;
+    if let Some(stats) = response.stats {
+        println!("{}", stats);
+        return Ok(());
+    }
+    if let Some(status) = response.status {
+        println!("enabled: {}", yes_no(status.enabled));
+        println!("focused: {}", yes_no(status.focused));
+        println!("delay:   {}ms", status.delay_ms);
+        println!("clicks:  {}", status.click_count);
+        println!("profile: {}", status.profile);
+        if !status.workers.is_empty() {
+            println!("workers:");
+            for worker in &status.workers {
+                println!("  {}", worker);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Record a mouse to an evemu file until interrupted
+fn record(device: Option<PathBuf>, output: &std::path::Path) -> Result<(), DoubleTapError> {
+    let device = match device {
+        Some(device) => device,
+        None => {
+            let devices = evemu::recordable_devices();
+            for (path, name) in &devices {
+                info!("Found {} ({})", path.display(), name);
+            }
+            devices.into_iter().next().map(|(path, _)| path).ok_or_else(|| {
+                DoubleTapError::Recording(
+                    "No readable mouse in /dev/input (are you in the 'input' group?)".to_string(),
+                )
+            })?
+        }
+    };
+    evemu::record(&device, output)
+}
+
+/// Print every diagnostic; exits with status 1 if any failed
+fn doctor(round_trip: bool) -> Result<(), DoubleTapError> {
+    let checks = doctor::run(round_trip);
+    for check in &checks {
+        println!("{}", check);
+    }
+    let failed = checks.iter().filter(|check| check.outcome == Outcome::Fail).count();
+    if failed == 0 {
+        println!("\nAll checks passed");
+        Ok(())
+    } else {
+        Err(DoubleTapError::Doctor(format!("{} of {} checks failed", failed, checks.len())))
+    }
+}
+
+/// Write (or with `print`, show) the udev rule and the systemd user unit
+fn install(devices: &[PathBuf], print: bool, udev: bool, unit: bool) -> Result<(), DoubleTapError> {
+    let rule = if udev {
+        let devices = if devices.is_empty() {
+            install::trigger_devices()
+        } else {
+            devices
+                .iter()
+                .map(|path| TriggerDevice::from_path(path))
+                .collect::<Result<_, _>>()?
+        };
+        if devices.is_empty() {
+            warn!("No mouse found; the rule only covers /dev/uinput");
+        }
+        for device in &devices {
+            info!("Trigger device: {} ({})", device.path.display(), device.name);
+        }
+        Some(install::udev_rule(&devices))
+    } else {
+        None
+    };
+    let unit = if unit {
+        let exe = std::env::current_exe()
+            .map_err(|e| DoubleTapError::Install(format!("can't find this executable: {}", e)))?;
+        Some(install::systemd_unit(&exe))
+    } else {
+        None
+    };
+
+    if print {
+        if let Some(rule) = &rule {
+            println!("# {}\n{}", install::UDEV_RULE_PATH, rule);
+        }
+        if let (Some(unit), Some(path)) = (&unit, install::unit_path()) {
+            println!("# {}\n{}", path.display(), unit);
+        }
+        return Ok(());
+    }
+
+    // The unit first: the rule may need a second run as root
+    if let Some(unit) = &unit {
+        install::install_unit(unit)?;
+    }
+    if let Some(rule) = &rule {
+        install::install_udev_rule(rule)?;
+    }
+    if unit.is_some() {
+        info!("Start it with: systemctl --user enable --now {}", install::UNIT_NAME);
+    }
+    Ok(())
+}
+
+/// Replay a recording through the decision pipeline
+///
+/// Focus is always granted and the cursor and game phase gates are off, so
+/// what's exercised is the timing: delay, feedback filter and rate limit.
+fn replay(file: &std::path::Path, emit: bool) -> Result<(), DoubleTapError> {
+    let config = Config::load()?;
+    let config = config.with_profile(&config.profile)?;
+    let recording = Recording::load(file)?;
+
+    let mut builder = Engine::builder()
+        .trigger(TriggerSource::Replay(recording))
+        .target(Target::Always)
+        .delay(std::time::Duration::from_millis(config.delay_ms));
+    if let Some(path) = config.event_log {
+        builder = builder.event_log(path);
+    }
+    if !emit {
+        builder = builder.sink(Box::new(DryRunSink::new()));
+    }
+    let engine = builder.build()?.start()?;
+    let state = engine.state().clone();
+    engine.wait();
+
+    println!("{}", state.stats().snapshot());
+    Ok(())
+}
+
+/// Print the statistics history summary for a date range
+fn show_history(
+    days: u32,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Result<(), DoubleTapError> {
+    let path = history::default_path()
+        .ok_or_else(|| DoubleTapError::History("Can't locate the data directory".to_string()))?;
+    let last_day = until.unwrap_or_else(|| Local::now().date_naive());
+    let (first_day, last_day) = history::date_range(days, since, last_day)?;
+
+    println!("{}", history::Summary::load(&path, first_day, last_day)?);
+    Ok(())
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Daemon mode, optionally wrapping a game command
+fn start_daemon(game_command: Option<&[String]>, dry_run: bool) -> Result<(), DoubleTapError> {
     info!("DoubleTap-RL starting...");
-    info!("Target window: '{}'", TARGET_WINDOW);
+
+    let config = Config::load()?;
+
+    match game_command {
+        None => run_daemon(&config, None, dry_run),
+        Some(command) => run_wrapped(&config, command, dry_run),
+    }
+}
+
+/// Launch-wrapper mode: spawn the game, run the daemon until it exits, and
+/// exit with the game's status so Steam sees the right result
+fn run_wrapped(config: &Config, command: &[String], dry_run: bool) -> Result<(), DoubleTapError> {
+    let mut game = GameProcess::spawn(command)?;
+    info!("Launched game (PID {})", game.pid());
+
+    // Never take the game down with us — if the daemon fails, keep waiting
+    if let Err(e) = run_daemon(config, Some(&mut game), dry_run) {
+        error!("{}", e);
+    }
+
+    info!("Waiting for game to exit...");
+    let status = game.wait()?;
+    info!("Game exited ({})", status);
+    std::process::exit(launcher::exit_code(status));
+}
+
+/// Run the listener → focus → auto-click pipeline until shutdown
+///
+/// With `game` set, focus is matched by the game's process tree and the
+/// daemon stops as soon as the game exits.
+fn run_daemon(
+    config: &Config,
+    mut game: Option<&mut GameProcess>,
+    dry_run: bool,
+) -> Result<(), DoubleTapError> {
+    // `base_config` is the file as loaded; `config` has the active profile applied
+    let mut base_config = config.clone();
+    let mut config = base_config.with_profile(&base_config.profile)?;
+    info!("Target window: '{}'", config.target_window);
 
     let running = Arc::new(AtomicBool::new(true));
-    let running_clone = running.clone();
-    ctrlc::set_handler(move || {
-        info!("Shutdown signal received");
-        running_clone.store(false, Ordering::SeqCst);
-    })
-    .expect("Failed to set Ctrl+C handler");
-
-    // Focus detection
-    let focus_detector = create_focus_detector(TARGET_WINDOW)?;
-    let focus_state = Arc::new(FocusState::new());
-    let _focus_handle = start_focus_poller(focus_detector, focus_state.clone(), running.clone());
-
-    // Start input listener FIRST — rdev scans /dev/input/event* on startup.
-    // Creating our virtual device AFTER ensures rdev won't read from it.
-    let (sender, receiver) = create_event_channel();
-    let listener = InputListener::new(sender);
-    let _listener_handle = listener.start();
-    std::thread::sleep(std::time::Duration::from_millis(200));
-
-    // Now create virtual device (rdev won't know about it)
-    let mut simulator = match InputSimulator::new() {
-        Ok(sim) => sim,
+    let signals = Signals::install()?;
+
+    let mut builder = Engine::builder().config(&config);
+    if let Some(game) = game.as_ref() {
+        builder = builder.target(Target::Process(game.pid()));
+    }
+    if dry_run {
+        builder = builder.sink(Box::new(DryRunSink::new()));
+    }
+    let engine = match builder.build()?.start() {
+        Ok(engine) => engine,
         Err(DoubleTapError::PermissionDenied) => {
             error!("Permission denied. Add your user to the 'input' group:");
             error!("  sudo usermod -aG input $USER");
             error!("Then logout and login again.");
+            error!("Or grant just /dev/uinput and your mice: sudo doubletap-rl install --udev");
             return Err(DoubleTapError::PermissionDenied);
         }
         Err(e) => return Err(e),
     };
 
+    // Enabled flag (independent of focus), delay and profile, shared with the
+    // hotkey, control socket, D-Bus service and tray
+    let state = engine.state().clone();
+    state.set_profile(&config.profile);
+    state.set_profile_names(base_config.profile_names());
+    let mut control_server =
+        match ControlServer::start(control::socket_path(), state.clone(), running.clone()) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                warn!("Control socket unavailable: {}", e);
+                None
+            }
+        };
+
+    // State-change events for notifications and hooks
+    let events = engine.events().clone();
+    let notifier_handle = if config.notifications.enabled {
+        match start_notifier(config.notifications.clone(), &events) {
+            Ok(handle) => Some(handle),
+            Err(e) => {
+                warn!("Desktop notifications unavailable: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // A dry run sends nothing, so it runs no hooks and records no history
+    let hook_handle = if dry_run || config.hooks.is_empty() {
+        None
+    } else {
+        Some(start_hook_runner(config.hooks.clone(), &events))
+    };
+
+    let tray_handle = if config.tray {
+        match start_tray(state.clone(), running.clone()) {
+            Ok(handle) => Some(handle),
+            Err(e) => {
+                warn!("Tray icon unavailable: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut dbus_service = if config.dbus_service {
+        match DbusService::start(state.clone(), running.clone()) {
+            Ok(service) => Some(service),
+            Err(e) => {
+                warn!("D-Bus service unavailable: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Long-term statistics, appended to the history file
+    let mut recorder = if config.history && !dry_run {
+        history::default_path().map(|path| {
+            HistoryRecorder::new(path, history::game_label(&config.target_window), &config.profile)
+        })
+    } else {
+        None
+    };
+
     info!("Press Ctrl+C to exit");
+    events.publish(DaemonEvent::Started);
+
+    // The virtual device exists by now, so a notify unit can count us ready
+    let mut systemd = SystemdNotifier::from_env();
+    if let Some(systemd) = systemd.as_mut() {
+        systemd.status(&service_status(&state));
+        systemd.ready();
+    }
 
-    while running.load(Ordering::SeqCst) {
-        match receiver.recv_timeout(std::time::Duration::from_millis(100)) {
-            Ok(_event) => {
-                if focus_state.is_focused() {
-                    // Brief delay for compositor to process physical release
-                    std::thread::sleep(std::time::Duration::from_millis(AUTO_CLICK_DELAY_MS));
-
-                    if let Err(e) = simulator.send_right_click() {
-                        error!("Auto-click failed: {}", e);
-                    } else {
-                        mark_auto_click_sent();
+    while running.load(Ordering::SeqCst) && engine.is_running() {
+        if let Some(status) = game.as_mut().and_then(|game| game.try_wait()) {
+            info!("Game exited ({}), shutting down", status);
+            break;
+        }
+
+        if state.take_reload_request() {
+            match Config::load() {
+                Ok(new_config) => {
+                    base_config = new_config;
+                    state.set_profile_names(base_config.profile_names());
+                    let active = state.profile();
+                    let profile = base_config.existing_profile(&active);
+                    if profile != active {
+                        warn!("Profile '{}' is gone after the reload, switching to '{}'", active, profile);
                     }
+                    apply_profile(&base_config, profile, &mut config, &state);
+                    info!("Configuration reloaded");
                 }
+                Err(e) => error!("Failed to reload configuration: {}", e),
             }
-            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
-            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
-                warn!("Input listener disconnected");
-                break;
+        }
+
+        if let Some(recorder) = recorder.as_mut() {
+            if let Err(e) = recorder.update(state.stats(), &state.profile()) {
+                warn!("{}", e);
+            }
+        }
+
+        if let Some(profile) = state.take_profile_request() {
+            apply_profile(&base_config, &profile, &mut config, &state);
+        }
+
+        if let Some(systemd) = systemd.as_mut() {
+            systemd.status(&service_status(&state));
+            systemd.watchdog();
+        }
+
+        for signal in signals.wait(std::time::Duration::from_millis(100)) {
+            match signal {
+                Signal::Shutdown(signo) => {
+                    info!("Shutdown signal received");
+                    // The game has its own process group, so a Ctrl-C in the
+                    // terminal or a `systemctl stop` never reaches it
+                    if let Some(game) = game.as_ref() {
+                        if let Err(e) = game.signal(signo) {
+                            warn!("{}", e);
+                        }
+                    }
+                    running.store(false, Ordering::SeqCst);
+                }
+                Signal::Reload => state.request_reload(),
+                Signal::Toggle => {
+                    state.toggle_enabled("SIGUSR1");
+                }
+                Signal::Disable => state.set_enabled(false, "SIGUSR2"),
             }
         }
     }
+    drop(signals);
 
     info!("DoubleTap-RL shutting down...");
-    Ok(())
+    if let Some(systemd) = systemd.as_ref() {
+        systemd.stopping();
+    }
+    info!("Session statistics:\n{}", state.stats().snapshot());
+    if let Some(recorder) = recorder.as_mut() {
+        if let Err(e) = recorder.record(state.stats()) {
+            warn!("{}", e);
+        }
+    }
+    events.publish(DaemonEvent::Stopped);
+
+    // Virtual device first, then the control interfaces (which watch
+    // `running`); dropping the bus lets the notifier and hook runner drain
+    // and spawn the stop hook
+    running.store(false, Ordering::SeqCst);
+    let failure = engine.failure();
+    engine.stop();
+    drop(events);
+
+    let mut workers = Workers::new();
+    let threads = [
+        ("control socket", control_server.as_mut().and_then(ControlServer::take_thread)),
+        ("D-Bus service", dbus_service.as_mut().and_then(DbusService::take_thread)),
+        ("tray icon", tray_handle),
+        ("notifier", notifier_handle),
+        ("hook runner", hook_handle),
+    ];
+    for (name, handle) in threads {
+        if let Some(handle) = handle {
+            workers.add(name, handle);
+        }
+    }
+    workers.join_until(Instant::now() + SHUTDOWN_TIMEOUT);
+
+    match failure {
+        Some(failure) => Err(DoubleTapError::Worker(failure)),
+        None => Ok(()),
+    }
+}
+
+/// Switch to the named profile, applying the settings that can change live
+///
+/// The delay and game phase list take effect immediately; gates and the
+/// toggle hotkey need a restart.
+fn apply_profile(base_config: &Config, profile: &str, config: &mut Config, state: &DaemonState) {
+    match base_config.with_profile(profile) {
+        Ok(new_config) => {
+            *config = new_config;
+            state.set_delay_ms(config.delay_ms);
+            state.set_game_phases(config.gates.game_phases.clone());
+            state.set_profile(profile);
+        }
+        Err(e) => error!("Failed to apply profile: {}", e),
+    }
+}
+
+/// One-line summary for `systemctl status`
+fn service_status(state: &DaemonState) -> String {
+    format!(
+        "Auto-click {}, target {}, profile {}",
+        if state.is_enabled() { "enabled" } else { "disabled" },
+        if state.is_focused() { "focused" } else { "not focused" },
+        state.profile()
+    )
 }
@@ -0,0 +1,237 @@
+//! Desktop notifications via org.freedesktop.Notifications
+//!
+//! Runs on its own thread, fed from the [`EventBus`]. Each event kind is
+//! rate-limited separately; when events arrive faster than the limit, only
+//! the latest one is shown once the interval has passed.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+use zbus::blocking::Connection;
+use zbus::zvariant::Value;
+
+use crate::events::{DaemonEvent, EventBus, EventKind};
+use crate::DoubleTapError;
+
+const NOTIFICATIONS_DEST: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const APP_NAME: &str = "DoubleTap-RL";
+const ICON: &str = "input-mouse";
+
+/// Notification settings (`[notifications]` in the config file)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// Master switch
+    pub enabled: bool,
+    /// Notify when auto-clicking is enabled/disabled
+    pub toggle: bool,
+    /// Notify when the game gains/loses focus
+    pub focus: bool,
+    /// Notify when the virtual device fails to send
+    pub device_error: bool,
+    /// Minimum time between two notifications of the same kind
+    pub min_interval_ms: u64,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            toggle: true,
+            focus: true,
+            device_error: true,
+            min_interval_ms: 2000,
+        }
+    }
+}
+
+impl NotificationConfig {
+    fn wants(&self, kind: EventKind) -> bool {
+        match kind {
+            EventKind::Lifecycle => false,
+            EventKind::Toggle => self.toggle,
+            EventKind::Focus => self.focus,
+            EventKind::DeviceError => self.device_error,
+        }
+    }
+}
+
+/// Sends notifications through the session bus
+struct NotificationSender {
+    connection: Connection,
+    /// Last notification id per kind, so a new one replaces the old
+    last_ids: HashMap<EventKind, u32>,
+}
+
+impl NotificationSender {
+    fn send(&mut self, event: &DaemonEvent) -> zbus::Result<()> {
+        let kind = event.kind();
+        let replaces_id = self.last_ids.get(&kind).copied().unwrap_or(0);
+
+        // Urgency: 1 = normal, 2 = critical
+        let urgency: u8 = if kind == EventKind::DeviceError { 2 } else { 1 };
+        let mut hints: HashMap<&str, Value<'_>> = HashMap::new();
+        hints.insert("urgency", Value::U8(urgency));
+
+        let reply = self.connection.call_method(
+            Some(NOTIFICATIONS_DEST),
+            NOTIFICATIONS_PATH,
+            Some(NOTIFICATIONS_DEST),
+            "Notify",
+            &(
+                APP_NAME,
+                replaces_id,
+                ICON,
+                APP_NAME,
+                event.describe(),
+                Vec::<&str>::new(),
+                hints,
+                -1i32,
+            ),
+        )?;
+        let id: u32 = reply.body().deserialize()?;
+        self.last_ids.insert(kind, id);
+        Ok(())
+    }
+}
+
+/// Per-kind rate limit that keeps only the latest event of each kind while
+/// it waits
+struct Coalescer {
+    min_interval: Duration,
+    last_sent: HashMap<EventKind, Instant>,
+    pending: HashMap<EventKind, DaemonEvent>,
+}
+
+impl Coalescer {
+    fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            last_sent: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Queue an event, replacing any still waiting of the same kind
+    fn push(&mut self, event: DaemonEvent) {
+        self.pending.insert(event.kind(), event);
+    }
+
+    /// Take the queued events whose kind is out of its interval at `now`,
+    /// counting them as sent
+    fn take_due(&mut self, now: Instant) -> Vec<DaemonEvent> {
+        let due: Vec<EventKind> = self
+            .pending
+            .keys()
+            .filter(|kind| {
+                self.last_sent
+                    .get(kind)
+                    .is_none_or(|sent| now.duration_since(*sent) >= self.min_interval)
+            })
+            .copied()
+            .collect();
+
+        due.into_iter()
+            .filter_map(|kind| {
+                self.last_sent.insert(kind, now);
+                self.pending.remove(&kind)
+            })
+            .collect()
+    }
+}
+
+/// Start a background thread that turns daemon events into notifications
+pub fn start_notifier(
+    config: NotificationConfig,
+    events: &EventBus,
+) -> Result<thread::JoinHandle<()>, DoubleTapError> {
+    let connection = Connection::session().map_err(|e| DoubleTapError::Dbus(e.to_string()))?;
+    let receiver = events.subscribe();
+    info!("Desktop notifications enabled");
+
+    Ok(thread::spawn(move || {
+        let mut sender = NotificationSender {
+            connection,
+            last_ids: HashMap::new(),
+        };
+        let mut coalescer = Coalescer::new(Duration::from_millis(config.min_interval_ms));
+
+        loop {
+            match receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok(event) if config.wants(event.kind()) => coalescer.push(event),
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            for event in coalescer.take_due(Instant::now()) {
+                if let Err(e) = sender.send(&event) {
+                    debug!("Failed to send notification: {}", e);
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(2);
+
+    #[test]
+    fn the_first_event_of_a_kind_goes_out_at_once() {
+        let mut coalescer = Coalescer::new(INTERVAL);
+        let now = Instant::now();
+        coalescer.push(DaemonEvent::Enabled(false));
+        assert_eq!(coalescer.take_due(now), [DaemonEvent::Enabled(false)]);
+        assert!(coalescer.take_due(now).is_empty());
+    }
+
+    #[test]
+    fn events_inside_the_interval_wait_and_only_the_latest_is_sent() {
+        let mut coalescer = Coalescer::new(INTERVAL);
+        let start = Instant::now();
+        coalescer.push(DaemonEvent::Focus(true));
+        coalescer.take_due(start);
+
+        coalescer.push(DaemonEvent::Focus(false));
+        coalescer.push(DaemonEvent::Focus(true));
+        coalescer.push(DaemonEvent::Focus(false));
+        assert!(coalescer.take_due(start + Duration::from_millis(1999)).is_empty());
+        assert_eq!(coalescer.take_due(start + INTERVAL), [DaemonEvent::Focus(false)]);
+        assert!(coalescer.take_due(start + INTERVAL * 2).is_empty());
+    }
+
+    #[test]
+    fn kinds_are_limited_separately() {
+        let mut coalescer = Coalescer::new(INTERVAL);
+        let start = Instant::now();
+        coalescer.push(DaemonEvent::Focus(true));
+        coalescer.take_due(start);
+
+        coalescer.push(DaemonEvent::Focus(false));
+        coalescer.push(DaemonEvent::DeviceError("write failed".to_string()));
+        let later = start + Duration::from_millis(500);
+        assert_eq!(coalescer.take_due(later), [DaemonEvent::DeviceError("write failed".to_string())]);
+        assert_eq!(coalescer.take_due(start + INTERVAL), [DaemonEvent::Focus(false)]);
+    }
+
+    #[test]
+    fn the_interval_restarts_from_the_last_send() {
+        let mut coalescer = Coalescer::new(INTERVAL);
+        let start = Instant::now();
+        coalescer.push(DaemonEvent::Enabled(true));
+        coalescer.take_due(start);
+        coalescer.push(DaemonEvent::Enabled(false));
+        let sent = start + Duration::from_secs(3);
+        assert_eq!(coalescer.take_due(sent).len(), 1);
+
+        coalescer.push(DaemonEvent::Enabled(true));
+        assert!(coalescer.take_due(sent + Duration::from_secs(1)).is_empty());
+        assert_eq!(coalescer.take_due(sent + INTERVAL), [DaemonEvent::Enabled(true)]);
+    }
+}
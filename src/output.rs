@@ -0,0 +1,227 @@
+//! Where auto-clicks go
+//!
+//! The pipeline writes through an [`OutputSink`]: normally the uinput
+//! [`InputSimulator`], or [`DryRunSink`] with `--dry-run`, which needs no
+//! `/dev/uinput` access and only logs what would have been sent.
+//! [`RecordingSink`] and [`MockSink`] keep the events in memory, so the
+//! pipeline can be driven and checked without a device.
+
+use chrono::{Local, SecondsFormat};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::info;
+
+use crate::engine::{Clock, SystemClock};
+use crate::input_simulator::InputSimulator;
+use crate::DoubleTapError;
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const SYN_REPORT: u16 = 0x00;
+pub const BTN_RIGHT: u16 = 0x111;
+
+/// One input event as written to the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputEvent {
+    pub event_type: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl OutputEvent {
+    /// Key or button event (value 1 = press, 0 = release)
+    pub const fn key(code: u16, value: i32) -> Self {
+        Self {
+            event_type: EV_KEY,
+            code,
+            value,
+        }
+    }
+
+    /// End of an event frame
+    pub const fn sync() -> Self {
+        Self {
+            event_type: EV_SYN,
+            code: SYN_REPORT,
+            value: 0,
+        }
+    }
+}
+
+impl fmt::Display for OutputEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.event_type, self.code, self.value) {
+            (EV_SYN, SYN_REPORT, _) => write!(f, "SYN_REPORT"),
+            (EV_KEY, BTN_RIGHT, 1) => write!(f, "BTN_RIGHT press"),
+            (EV_KEY, BTN_RIGHT, 0) => write!(f, "BTN_RIGHT release"),
+            (event_type, code, value) => {
+                write!(f, "type {:#04x} code {:#05x} value {}", event_type, code, value)
+            }
+        }
+    }
+}
+
+/// A right-click: press + sync + release + sync
+pub const RIGHT_CLICK: [OutputEvent; 4] = [
+    OutputEvent::key(BTN_RIGHT, 1),
+    OutputEvent::sync(),
+    OutputEvent::key(BTN_RIGHT, 0),
+    OutputEvent::sync(),
+];
+
+/// Destination for auto-click events
+pub trait OutputSink: Send {
+    /// Write events in order, stopping at the first failure
+    fn emit(&mut self, events: &[OutputEvent]) -> Result<(), DoubleTapError>;
+
+    /// Send one right-click (press and release)
+    fn send_right_click(&mut self) -> Result<(), DoubleTapError> {
+        self.emit(&RIGHT_CLICK)
+    }
+}
+
+impl OutputSink for InputSimulator {
+    fn emit(&mut self, events: &[OutputEvent]) -> Result<(), DoubleTapError> {
+        for event in events {
+            self.write_event(event.event_type, event.code, event.value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Logs auto-clicks instead of sending them
+#[derive(Debug, Default)]
+pub struct DryRunSink;
+
+impl DryRunSink {
+    pub fn new() -> Self {
+        info!("Dry run: no virtual device, auto-clicks are only logged");
+        Self
+    }
+}
+
+impl OutputSink for DryRunSink {
+    fn emit(&mut self, events: &[OutputEvent]) -> Result<(), DoubleTapError> {
+        let events: Vec<String> = events
+            .iter()
+            .filter(|event| **event != OutputEvent::sync())
+            .map(|event| event.to_string())
+            .collect();
+        info!(
+            "Dry run: would send {} at {}",
+            events.join(" + "),
+            Local::now().to_rfc3339_opts(SecondsFormat::Micros, false)
+        );
+        Ok(())
+    }
+}
+
+/// An event captured by a [`RecordingSink`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmittedEvent {
+    /// When the sink received it
+    pub time: Instant,
+    pub event: OutputEvent,
+}
+
+/// Keeps every emitted event in memory, with its timestamp
+///
+/// Clones share the same buffer, so one can go into the pipeline while
+/// another is kept for inspection.
+#[derive(Debug, Clone)]
+pub struct RecordingSink {
+    events: Arc<Mutex<Vec<EmittedEvent>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RecordingSink {
+    pub fn new() -> Self {
+        Self {
+            events: Arc::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Timestamp events with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Everything emitted so far
+    pub fn events(&self) -> Vec<EmittedEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Number of right-click presses emitted so far
+    pub fn click_count(&self) -> usize {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|emitted| emitted.event == OutputEvent::key(BTN_RIGHT, 1))
+            .count()
+    }
+
+    pub fn clear(&self) {
+        self.events.lock().unwrap().clear();
+    }
+}
+
+impl Default for RecordingSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutputSink for RecordingSink {
+    fn emit(&mut self, events: &[OutputEvent]) -> Result<(), DoubleTapError> {
+        let time = self.clock.now();
+        self.events
+            .lock()
+            .unwrap()
+            .extend(events.iter().map(|&event| EmittedEvent { time, event }));
+        Ok(())
+    }
+}
+
+/// Recording sink whose writes can be made to fail, like a device that
+/// went away
+#[derive(Debug, Clone, Default)]
+pub struct MockSink {
+    recording: RecordingSink,
+    failing: Arc<AtomicBool>,
+}
+
+impl MockSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Timestamp events with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.recording = self.recording.with_clock(clock);
+        self
+    }
+
+    /// Make subsequent writes fail (true) or succeed again (false)
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+
+    /// Events that were written successfully
+    pub fn recording(&self) -> &RecordingSink {
+        &self.recording
+    }
+}
+
+impl OutputSink for MockSink {
+    fn emit(&mut self, events: &[OutputEvent]) -> Result<(), DoubleTapError> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(DoubleTapError::SendEvent("mock sink failure".to_string()));
+        }
+        self.recording.emit(events)
+    }
+}
@@ -0,0 +1,303 @@
+//! Trigger decision pipeline
+//!
+//! Feeds each trigger release, along with the current focus, gate and
+//! daemon state, to the [`DecisionEngine`] and carries out what it decides —
+//! suppress the release, or send an auto-click after the configured delay —
+//! counting the outcome and writing the optional event log record. Used by
+//! the daemon and by `replay`.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, error, warn};
+
+use crate::cursor_gate::CursorState;
+use crate::engine::{Action, Clock, DecisionEngine, Input, SystemClock, Trigger};
+use crate::event_log::{Decision, DecisionSnapshot, EventLog, TriggerRecord};
+use crate::events::{DaemonEvent, EventBus};
+use crate::game_state::GameState;
+use crate::input_listener::RightClickEvent;
+use crate::output::OutputSink;
+use crate::state::DaemonState;
+use crate::stats::SuppressReason;
+
+/// Turns trigger releases into auto-clicks
+pub struct TriggerPipeline {
+    state: DaemonState,
+    cursor_state: Arc<CursorState>,
+    game_state: Arc<GameState>,
+    /// The virtual device, or a dry-run stand-in
+    sink: Box<dyn OutputSink>,
+    events: EventBus,
+    event_log: Option<EventLog>,
+    engine: DecisionEngine,
+    clock: Arc<dyn Clock>,
+}
+
+impl TriggerPipeline {
+    pub fn new(
+        state: DaemonState,
+        cursor_state: Arc<CursorState>,
+        game_state: Arc<GameState>,
+        sink: Box<dyn OutputSink>,
+        events: EventBus,
+    ) -> Self {
+        let engine = DecisionEngine::new(Duration::from_millis(state.delay_ms()));
+        Self {
+            state,
+            cursor_state,
+            game_state,
+            sink,
+            events,
+            event_log: None,
+            engine,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Also append a record for every trigger to `event_log`
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    /// Take the time from `clock` instead of the system clock; trigger
+    /// timestamps must then come from the same clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Decide on one trigger release, sending the auto-click if it passes
+    /// every check
+    pub fn handle(&mut self, event: RightClickEvent) {
+        self.sync();
+        self.state.stats().record_trigger();
+
+        let trigger = Trigger {
+            time: event.timestamp,
+            device: event.device,
+            kernel_time: event.kernel_time,
+        };
+        for action in self.engine.handle(event.timestamp, Input::Trigger(trigger)) {
+            self.perform(action);
+        }
+    }
+
+    /// Drop a click that was cut short, e.g. by a panic in the sink, so it
+    /// doesn't rate-limit every later trigger
+    pub fn recover(&mut self) {
+        self.engine = DecisionEngine::new(self.engine.delay());
+    }
+
+    /// Bring the engine up to date with the shared daemon state
+    fn sync(&mut self) {
+        let now = self.clock.now();
+        let focus = self.state.focus();
+        let inputs = [
+            Input::SetEnabled(self.state.is_enabled()),
+            Input::SetDelay(Duration::from_millis(self.state.delay_ms())),
+            Input::SetGamePhases(self.state.game_phases()),
+            Input::Focus {
+                focused: focus.is_focused(),
+                pointer_inside: focus.is_pointer_inside(),
+            },
+            Input::CursorVisible(self.cursor_state.is_visible()),
+            Input::GamePhase(self.game_state.phase()),
+        ];
+        for input in inputs {
+            self.engine.handle(now, input);
+        }
+    }
+
+    fn perform(&mut self, action: Action) {
+        let stats = self.state.stats().clone();
+        match action {
+            Action::NaturalDoubleTap => stats.record_natural_double_tap(),
+            Action::Suppress {
+                trigger,
+                reason,
+                state,
+            } => {
+                stats.record_suppressed(reason);
+                self.log(trigger, state, Some(reason), None, None);
+            }
+            Action::Schedule {
+                trigger,
+                at,
+                delay,
+                state,
+            } => {
+                self.clock
+                    .sleep(at.saturating_duration_since(self.clock.now()));
+                let mut emitted = None;
+                for action in self.engine.handle(self.clock.now(), Input::Tick) {
+                    if let Action::Click { trigger } = action {
+                        emitted = Some(self.click(&trigger));
+                    }
+                }
+                self.log(trigger, state, None, Some(delay), emitted);
+            }
+            Action::Click { trigger } => {
+                self.click(&trigger);
+            }
+        }
+    }
+
+    /// Send the auto-click for `trigger` and report the result to the engine
+    fn click(&mut self, trigger: &Trigger) -> Emitted {
+        let stats = self.state.stats().clone();
+        let result = self.sink.send_right_click();
+        let now = self.clock.now();
+        let latency = now.saturating_duration_since(trigger.time);
+        let error = match result {
+            Ok(()) => {
+                self.engine.handle(now, Input::ClickSent);
+                debug!(
+                    "Auto-click sent {:.1}ms after trigger",
+                    latency.as_secs_f64() * 1000.0
+                );
+                stats.record_auto_click(latency);
+                None
+            }
+            Err(e) => {
+                self.engine.handle(now, Input::ClickFailed);
+                error!("Auto-click failed: {}", e);
+                stats.record_send_failure();
+                self.events.publish(DaemonEvent::DeviceError(e.to_string()));
+                Some(e.to_string())
+            }
+        };
+        Emitted {
+            time: SystemTime::now(),
+            latency,
+            error,
+        }
+    }
+
+    /// Append the event log record for one trigger
+    fn log(
+        &mut self,
+        trigger: Trigger,
+        state: DecisionSnapshot,
+        reason: Option<SuppressReason>,
+        delay: Option<Duration>,
+        emitted: Option<Emitted>,
+    ) {
+        let Some(log) = self.event_log.as_mut() else {
+            return;
+        };
+        let age = self.clock.now().saturating_duration_since(trigger.time);
+        let record = TriggerRecord {
+            time: TriggerRecord::format_time(SystemTime::now() - age),
+            device: trigger.device,
+            kernel_time: trigger.kernel_time.map(TriggerRecord::format_time),
+            state,
+            decision: match reason {
+                Some(_) => Decision::Suppressed,
+                None => Decision::Fired,
+            },
+            reason,
+            delay_ms: delay.map(|delay| delay.as_millis() as u64),
+            emit_time: emitted
+                .as_ref()
+                .map(|emitted| TriggerRecord::format_time(emitted.time)),
+            latency_us: emitted
+                .as_ref()
+                .map(|emitted| emitted.latency.as_micros() as u64),
+            error: emitted.and_then(|emitted| emitted.error),
+        };
+        if let Err(e) = log.write(&record) {
+            warn!("{}", e);
+        }
+    }
+}
+
+/// How an auto-click went out
+struct Emitted {
+    time: SystemTime,
+    latency: Duration,
+    error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::VirtualClock;
+    use crate::focus_detector::FocusState;
+    use crate::output::{MockSink, RecordingSink, RIGHT_CLICK};
+    use std::time::Instant;
+
+    /// Pipeline with the target focused, writing to `sink`
+    fn pipeline(
+        sink: Box<dyn OutputSink>,
+        clock: Arc<VirtualClock>,
+    ) -> (TriggerPipeline, DaemonState, EventBus) {
+        let focus = Arc::new(FocusState::new());
+        focus.set_focused(true);
+        let state = DaemonState::new(focus, 15);
+        let events = EventBus::new();
+        let pipeline = TriggerPipeline::new(
+            state.clone(),
+            Arc::new(CursorState::new()),
+            Arc::new(GameState::new()),
+            sink,
+            events.clone(),
+        )
+        .with_clock(clock);
+        (pipeline, state, events)
+    }
+
+    fn release(time: Instant) -> RightClickEvent {
+        RightClickEvent {
+            timestamp: time,
+            device: None,
+            kernel_time: None,
+        }
+    }
+
+    #[test]
+    fn passing_trigger_emits_exactly_one_right_click() {
+        let clock = Arc::new(VirtualClock::new());
+        let sink = RecordingSink::new().with_clock(clock.clone());
+        let (mut pipeline, state, _) = pipeline(Box::new(sink.clone()), clock.clone());
+
+        let start = clock.now();
+        pipeline.handle(release(start));
+
+        let emitted = sink.events();
+        let events: Vec<_> = emitted.iter().map(|emitted| emitted.event).collect();
+        assert_eq!(events, RIGHT_CLICK);
+        assert!(emitted
+            .iter()
+            .all(|emitted| emitted.time == start + Duration::from_millis(15)));
+        assert_eq!(state.stats().snapshot().auto_clicks, 1);
+    }
+
+    #[test]
+    fn failed_send_is_counted_and_not_rate_limited() {
+        let clock = Arc::new(VirtualClock::new());
+        let sink = MockSink::new().with_clock(clock.clone());
+        let (mut pipeline, state, events) = pipeline(Box::new(sink.clone()), clock.clone());
+        let received = events.subscribe();
+
+        sink.set_failing(true);
+        pipeline.handle(release(clock.now()));
+
+        let stats = state.stats().snapshot();
+        assert_eq!(stats.send_failures, 1);
+        assert_eq!(stats.auto_clicks, 0);
+        assert!(sink.recording().events().is_empty());
+        assert!(matches!(received.try_recv(), Ok(DaemonEvent::DeviceError(_))));
+
+        // Straight away: the failed click mustn't count as our last one
+        sink.set_failing(false);
+        clock.advance(Duration::from_millis(5));
+        pipeline.handle(release(clock.now()));
+
+        let stats = state.stats().snapshot();
+        assert_eq!(stats.suppressed_rate_limit, 0);
+        assert_eq!(stats.suppressed_feedback, 0);
+        assert_eq!(stats.auto_clicks, 1);
+        assert_eq!(sink.recording().click_count(), 1);
+    }
+}
@@ -0,0 +1,490 @@
+//! Embeddable auto-click engine
+//!
+//! Everything between a trigger release and the auto-click — listener,
+//! focus poller, optional gates, virtual device and decision pipeline —
+//! wired together behind one builder, so other tools can host DoubleTap-RL
+//! in-process:
+//!
+//! ```no_run
+//! use doubletap_rl::runtime::{Engine, Target};
+//! use std::time::{Duration, Instant};
+//!
+//! let engine = Engine::builder()
+//!     .target(Target::Process(4242))
+//!     .delay(Duration::from_millis(15))
+//!     .build()?
+//!     .start()?;
+//! let events = engine.subscribe();
+//! // ...
+//! engine.stop();
+//! # Ok::<(), doubletap_rl::DoubleTapError>(())
+//! ```
+//!
+//! The daemon itself is this engine plus the control socket, D-Bus service,
+//! tray, notifications, hooks and history around it.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::{Config, GateConfig, DEFAULT_DELAY_MS, DEFAULT_TARGET_WINDOW};
+use crate::cursor_gate::{start_cursor_watcher, CursorState};
+use crate::evemu::Recording;
+use crate::event_log::EventLog;
+use crate::events::{DaemonEvent, EventBus};
+use crate::focus_detector::{
+    create_focus_detector, start_focus_poller, AlwaysFocused, FocusDetector, FocusState,
+    ROCKET_LEAGUE_APP_ID,
+};
+use crate::game_state::{start_game_state_tailer, GameState};
+use crate::hotkey::Hotkey;
+use crate::input_listener::{create_event_channel, InputListener, ListenerEvent};
+use crate::input_simulator::InputSimulator;
+use crate::output::OutputSink;
+use crate::pipeline::TriggerPipeline;
+use crate::shutdown::{Workers, SHUTDOWN_TIMEOUT};
+use crate::state::DaemonState;
+use crate::stats::StatsSnapshot;
+use crate::supervisor::Supervisor;
+use crate::DoubleTapError;
+
+/// Where trigger releases come from
+#[derive(Debug, Clone, Default)]
+pub enum TriggerSource {
+    /// The real mouse, through rdev
+    #[default]
+    Devices,
+    /// An evemu recording, played back with its original timing; the engine
+    /// stops by itself once it has finished
+    Replay(Recording),
+}
+
+/// Which window counts as the game
+pub enum Target {
+    /// EWMH window-title match (gamescope sessions match the Steam AppID)
+    Window(String),
+    /// Any window belonging to this process or its children
+    Process(u32),
+    /// Always focused, e.g. for replays
+    Always,
+    /// A detector of your own
+    Custom(Box<dyn FocusDetector>),
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Self::Window(DEFAULT_TARGET_WINDOW.to_string())
+    }
+}
+
+/// Settings for an [`Engine`]; see [`Engine::builder`]
+#[derive(Default)]
+pub struct EngineBuilder {
+    trigger: TriggerSource,
+    target: Target,
+    delay: Option<Duration>,
+    toggle_hotkey: Option<Hotkey>,
+    gates: GateConfig,
+    tail_launch_log: bool,
+    sink: Option<Box<dyn OutputSink>>,
+    event_log: Option<PathBuf>,
+}
+
+impl EngineBuilder {
+    /// Take the target window, delay, toggle hotkey, gates and event log
+    /// from a (profile-applied) config
+    pub fn config(mut self, config: &Config) -> Self {
+        self.target = Target::Window(config.target_window.clone());
+        self.delay = Some(Duration::from_millis(config.delay_ms));
+        self.toggle_hotkey = config.toggle_hotkey.clone();
+        self.gates = config.gates.clone();
+        self.tail_launch_log = config.uses_game_phases();
+        self.event_log = config.event_log.clone();
+        self
+    }
+
+    pub fn trigger(mut self, trigger: TriggerSource) -> Self {
+        self.trigger = trigger;
+        self
+    }
+
+    pub fn target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Delay between the physical release and the auto-click
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Also watch the keyboard for a chord that toggles auto-clicking
+    pub fn toggle_hotkey(mut self, hotkey: Hotkey) -> Self {
+        self.toggle_hotkey = Some(hotkey);
+        self
+    }
+
+    /// Cursor, pointer and game phase gates
+    pub fn gates(mut self, gates: GateConfig) -> Self {
+        self.tail_launch_log |= !gates.game_phases.is_empty();
+        self.gates = gates;
+        self
+    }
+
+    /// Send auto-clicks here instead of a new uinput device
+    pub fn sink(mut self, sink: Box<dyn OutputSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Append every trigger decision to this JSON-lines file
+    pub fn event_log(mut self, path: PathBuf) -> Self {
+        self.event_log = Some(path);
+        self
+    }
+
+    /// Set up focus detection; nothing runs until [`Engine::start`]
+    pub fn build(self) -> Result<Engine, DoubleTapError> {
+        let focus_detector = match self.target {
+            Target::Window(title) => create_focus_detector(&title, ROCKET_LEAGUE_APP_ID, None)?,
+            Target::Process(pid) => {
+                create_focus_detector(DEFAULT_TARGET_WINDOW, ROCKET_LEAGUE_APP_ID, Some(pid))?
+            }
+            Target::Always => Box::new(AlwaysFocused),
+            Target::Custom(detector) => detector,
+        };
+        let focus_detector: Arc<dyn FocusDetector> = Arc::from(focus_detector);
+        Ok(Engine {
+            trigger: self.trigger,
+            focus_detector,
+            delay: self.delay.unwrap_or(Duration::from_millis(DEFAULT_DELAY_MS)),
+            toggle_hotkey: self.toggle_hotkey,
+            gates: self.gates,
+            tail_launch_log: self.tail_launch_log,
+            sink: self.sink,
+            event_log: self.event_log,
+        })
+    }
+}
+
+/// A configured engine, ready to [`start`](Engine::start)
+pub struct Engine {
+    trigger: TriggerSource,
+    focus_detector: Arc<dyn FocusDetector>,
+    delay: Duration,
+    toggle_hotkey: Option<Hotkey>,
+    gates: GateConfig,
+    tail_launch_log: bool,
+    sink: Option<Box<dyn OutputSink>>,
+    event_log: Option<PathBuf>,
+}
+
+impl Engine {
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
+    /// Start the worker threads and the virtual device
+    ///
+    /// The listener, focus poller and pipeline are restarted if they die;
+    /// see [`supervisor`](crate::supervisor).
+    ///
+    /// Fails with [`DoubleTapError::PermissionDenied`] when `/dev/uinput`
+    /// can't be opened and no other sink was given.
+    pub fn start(self) -> Result<EngineHandle, DoubleTapError> {
+        let running = Arc::new(AtomicBool::new(true));
+
+        let focus_state = Arc::new(FocusState::new());
+        let state = DaemonState::new(focus_state.clone(), self.delay.as_millis() as u64);
+        let mut supervisor = Supervisor::new(state.health().clone());
+        {
+            let detector = self.focus_detector;
+            let running = running.clone();
+            let pointer = self.gates.pointer;
+            supervisor.supervise("focus poller", move || {
+                start_focus_poller(
+                    Box::new(detector.clone()),
+                    focus_state.clone(),
+                    running.clone(),
+                    pointer,
+                )
+            });
+        }
+        state.set_game_phases(self.gates.game_phases.clone());
+        let events = EventBus::new();
+
+        // Optional cursor gate — suppress auto-clicks while a cursor is shown
+        let cursor_state = Arc::new(CursorState::new());
+        if self.gates.cursor {
+            match start_cursor_watcher(cursor_state.clone(), running.clone()) {
+                Ok(handle) => {
+                    info!("Cursor gate enabled");
+                    let mut first = Some(handle);
+                    let cursor_state = cursor_state.clone();
+                    let running = running.clone();
+                    supervisor.supervise("cursor watcher", move || {
+                        first.take().unwrap_or_else(|| {
+                            // A failed reconnect counts as the watcher dying again
+                            start_cursor_watcher(cursor_state.clone(), running.clone()).unwrap_or_else(|e| {
+                                warn!("Cursor gate unavailable: {}", e);
+                                thread::spawn(|| {})
+                            })
+                        })
+                    });
+                }
+                Err(e) => warn!("Cursor gate unavailable: {}", e),
+            }
+        }
+
+        // Optional game phase gate — tail Launch.log for map loads
+        let game_state = Arc::new(GameState::new());
+        if self.tail_launch_log {
+            info!("Game phase gate enabled: {:?}", self.gates.game_phases);
+            supervisor.watch(
+                "game state tailer",
+                start_game_state_tailer(
+                    self.gates.launch_log.clone(),
+                    game_state.clone(),
+                    running.clone(),
+                ),
+            );
+        }
+
+        // Start input listener FIRST — rdev scans /dev/input/event* on startup.
+        // Creating our virtual device AFTER ensures rdev won't read from it.
+        let (sender, receiver) = create_event_channel();
+        let mut listener = InputListener::new(sender).with_running(running.clone());
+        if let Some(hotkey) = self.toggle_hotkey {
+            info!("Toggle hotkey: {}", hotkey);
+            listener = listener.with_toggle_hotkey(hotkey);
+        }
+        if self.event_log.is_some() {
+            // The event log records the source device and kernel timestamp
+            listener = listener.with_device_annotations();
+        }
+        let replaying = matches!(self.trigger, TriggerSource::Replay(_));
+        if let TriggerSource::Replay(recording) = self.trigger {
+            listener = listener.replaying(recording);
+        }
+
+        let sink = if replaying {
+            // Replays start once the device exists and the focus poller has
+            // seen the target, so neither skews the first latencies
+            let sink = create_sink(self.sink);
+            if sink.is_ok() {
+                thread::sleep(Duration::from_millis(200));
+                supervisor.watch("input listener", listener.start());
+            }
+            sink
+        } else {
+            supervisor.supervise("input listener", move || listener.clone().start());
+            thread::sleep(Duration::from_millis(200));
+            // Now create virtual device (rdev won't know about it)
+            create_sink(self.sink)
+        };
+        let sink = match sink {
+            Ok(sink) => sink,
+            Err(e) => {
+                running.store(false, Ordering::SeqCst);
+                let _ = supervisor.start(running).join();
+                return Err(e);
+            }
+        };
+
+        let mut pipeline = TriggerPipeline::new(
+            state.clone(),
+            cursor_state,
+            game_state,
+            sink,
+            events.clone(),
+        );
+        if let Some(path) = &self.event_log {
+            match EventLog::open(path) {
+                Ok(log) => {
+                    info!("Event log: {:?}", path);
+                    pipeline = pipeline.with_event_log(log);
+                }
+                Err(e) => warn!("Event log unavailable: {}", e),
+            }
+        }
+
+        // Shared so a restarted pipeline keeps its device and channel
+        let pipeline = Arc::new(Mutex::new((pipeline, receiver)));
+        {
+            let state = state.clone();
+            let events = events.clone();
+            let running = running.clone();
+            supervisor.supervise("trigger pipeline", move || {
+                let pipeline = pipeline.clone();
+                let state = state.clone();
+                let events = events.clone();
+                let running = running.clone();
+                thread::spawn(move || {
+                    let mut guard = pipeline.lock().unwrap_or_else(|poisoned| {
+                        let mut guard = poisoned.into_inner();
+                        guard.0.recover();
+                        guard
+                    });
+                    let (pipeline, receiver) = &mut *guard;
+                    run(pipeline, receiver, &state, &events, &running, replaying);
+                })
+            });
+        }
+
+        // Added last, so shutdown joins the pipeline (and removes the
+        // device) first
+        let supervisor = supervisor.start(running.clone());
+
+        Ok(EngineHandle {
+            state,
+            events,
+            running,
+            supervisor: Some(supervisor),
+        })
+    }
+}
+
+/// The given sink, or a new uinput device
+fn create_sink(sink: Option<Box<dyn OutputSink>>) -> Result<Box<dyn OutputSink>, DoubleTapError> {
+    match sink {
+        Some(sink) => Ok(sink),
+        None => Ok(Box::new(InputSimulator::new()?)),
+    }
+}
+
+/// Feed listener events to the pipeline until stopped
+fn run(
+    pipeline: &mut TriggerPipeline,
+    receiver: &mpsc::Receiver<ListenerEvent>,
+    state: &DaemonState,
+    events: &EventBus,
+    running: &AtomicBool,
+    replaying: bool,
+) {
+    let mut last_enabled = state.is_enabled();
+    let mut last_focused = state.is_focused();
+
+    while running.load(Ordering::SeqCst) {
+        let enabled = state.is_enabled();
+        if enabled != last_enabled {
+            events.publish(DaemonEvent::Enabled(enabled));
+            last_enabled = enabled;
+        }
+
+        let focused = state.is_focused();
+        if focused != last_focused {
+            info!("Target window {}", if focused { "focused" } else { "unfocused" });
+            events.publish(DaemonEvent::Focus(focused));
+            last_focused = focused;
+        }
+
+        match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(ListenerEvent::ToggleHotkey) => {
+                state.toggle_enabled("toggle hotkey");
+            }
+            Ok(ListenerEvent::RightClick(event)) => pipeline.handle(event),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                if !replaying {
+                    warn!("Input listener disconnected");
+                }
+                running.store(false, Ordering::SeqCst);
+                break;
+            }
+        }
+    }
+}
+
+/// A running engine
+///
+/// Dropping the handle stops the engine, like [`stop`](EngineHandle::stop).
+pub struct EngineHandle {
+    state: DaemonState,
+    events: EventBus,
+    running: Arc<AtomicBool>,
+    /// Watches the workers, and joins them once stopped
+    supervisor: Option<thread::JoinHandle<()>>,
+}
+
+impl EngineHandle {
+    /// Stop the engine and remove the virtual device
+    ///
+    /// The pipeline goes first, so the device is gone (with any held button
+    /// released) before the listener and pollers are joined. Threads still
+    /// running after [`SHUTDOWN_TIMEOUT`] are left behind with a warning.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    /// Wait for the engine to stop by itself, e.g. at the end of a replay
+    pub fn wait(mut self) {
+        if let Some(supervisor) = self.supervisor.take() {
+            let _ = supervisor.join();
+        }
+    }
+
+    /// Whether the engine is still running; it stops by itself when its
+    /// trigger source goes away
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Why the engine stopped by itself, if a worker kept dying
+    pub fn failure(&self) -> Option<String> {
+        self.state.health().failure()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state.is_enabled()
+    }
+
+    /// Enable or disable auto-clicking
+    pub fn set_enabled(&self, enabled: bool) {
+        self.state.set_enabled(enabled, "engine handle");
+    }
+
+    pub fn set_delay(&self, delay: Duration) {
+        self.state.set_delay_ms(delay.as_millis() as u64);
+    }
+
+    /// This session's decision counters and latency histogram
+    pub fn stats(&self) -> StatsSnapshot {
+        self.state.stats().snapshot()
+    }
+
+    /// Receive state-change events from now on
+    pub fn subscribe(&self) -> mpsc::Receiver<DaemonEvent> {
+        self.events.subscribe()
+    }
+
+    /// The engine's live state, for control interfaces
+    pub fn state(&self) -> &DaemonState {
+        &self.state
+    }
+
+    /// The engine's event bus, for publishing alongside its own events
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    fn shutdown(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // The supervisor gives its workers SHUTDOWN_TIMEOUT; allow it a
+        // little more to notice the flag and hand back
+        let mut workers = Workers::new();
+        if let Some(supervisor) = self.supervisor.take() {
+            workers.add("supervisor", supervisor);
+        }
+        workers.join_until(Instant::now() + SHUTDOWN_TIMEOUT + Duration::from_millis(500));
+    }
+}
+
+impl Drop for EngineHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
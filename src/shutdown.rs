@@ -0,0 +1,51 @@
+//! Ordered, bounded teardown of background threads
+//!
+//! Workers watch a shared `running` flag (or their event channel closing)
+//! and finish on their own; [`Workers`] then joins them in the order they
+//! were added, giving up with a warning on any that outlive the deadline so
+//! a hung thread can't keep the process from exiting.
+
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// How long shutdown waits for each group of threads
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Named background threads, joined in the order they were added
+#[derive(Default)]
+pub struct Workers {
+    threads: Vec<(&'static str, thread::JoinHandle<()>)>,
+}
+
+impl Workers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: &'static str, handle: thread::JoinHandle<()>) {
+        self.threads.push((name, handle));
+    }
+
+    /// Join every thread, leaving behind (with a warning) those still
+    /// running at `deadline`; returns whether all of them finished
+    pub fn join_until(self, deadline: Instant) -> bool {
+        let mut all_finished = true;
+        for (name, handle) in self.threads {
+            while !handle.is_finished() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+            if !handle.is_finished() {
+                warn!("Shutdown timed out waiting for the {}, leaving it behind", name);
+                all_finished = false;
+                continue;
+            }
+            if handle.join().is_err() {
+                warn!("The {} panicked", name);
+            } else {
+                debug!("Stopped the {}", name);
+            }
+        }
+        all_finished
+    }
+}
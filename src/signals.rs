@@ -0,0 +1,149 @@
+//! Unix signals for the daemon
+//!
+//! SIGINT and SIGTERM stop the daemon, SIGHUP reloads the config file,
+//! SIGUSR1 toggles auto-clicking and SIGUSR2 disables it, so service
+//! managers and window-manager keybindings (`pkill -USR1 doubletap-rl`) can
+//! drive it. The handlers only write the signal number to a self-pipe; the
+//! main loop reads it back in [`Signals::wait`].
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::DoubleTapError;
+
+/// Write end of the self-pipe, for the signal handler (-1 when none)
+static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+const HANDLED: [libc::c_int; 5] = [
+    libc::SIGINT,
+    libc::SIGTERM,
+    libc::SIGHUP,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+];
+
+/// What a received signal asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// SIGINT or SIGTERM, kept so it can be passed on to a wrapped game
+    Shutdown(libc::c_int),
+    /// SIGHUP
+    Reload,
+    /// SIGUSR1
+    Toggle,
+    /// SIGUSR2
+    Disable,
+}
+
+impl Signal {
+    fn from_raw(signal: libc::c_int) -> Option<Self> {
+        match signal {
+            libc::SIGINT | libc::SIGTERM => Some(Signal::Shutdown(signal)),
+            libc::SIGHUP => Some(Signal::Reload),
+            libc::SIGUSR1 => Some(Signal::Toggle),
+            libc::SIGUSR2 => Some(Signal::Disable),
+            _ => None,
+        }
+    }
+}
+
+/// Installed signal handlers; dropping restores the default dispositions
+pub struct Signals {
+    read: OwnedFd,
+    _write: OwnedFd,
+}
+
+impl Signals {
+    /// Route the handled signals to this process's self-pipe
+    ///
+    /// Only one instance should exist at a time.
+    pub fn install() -> Result<Self, DoubleTapError> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } != 0 {
+            return Err(DoubleTapError::Signal(io::Error::last_os_error().to_string()));
+        }
+        let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        WRITE_FD.store(write.as_raw_fd(), Ordering::SeqCst);
+
+        for signal in HANDLED {
+            let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            if unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) } != 0 {
+                let error = io::Error::last_os_error();
+                restore_defaults();
+                return Err(DoubleTapError::Signal(error.to_string()));
+            }
+        }
+
+        Ok(Self {
+            read,
+            _write: write,
+        })
+    }
+
+    /// Wait up to `timeout` for signals, returning those received in order
+    pub fn wait(&self, timeout: Duration) -> Vec<Signal> {
+        let mut pollfd = libc::pollfd {
+            fd: self.read.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as i32) } <= 0 {
+            return Vec::new();
+        }
+
+        let mut signals = Vec::new();
+        let mut buf = [0u8; 32];
+        loop {
+            let read = unsafe {
+                libc::read(self.read.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len())
+            };
+            if read <= 0 {
+                break;
+            }
+            for &raw in &buf[..read as usize] {
+                if let Some(signal) = Signal::from_raw(raw as libc::c_int) {
+                    debug!("Received {:?} signal", signal);
+                    signals.push(signal);
+                }
+            }
+        }
+        signals
+    }
+}
+
+impl Drop for Signals {
+    fn drop(&mut self) {
+        // Back to the defaults, so a second Ctrl+C during shutdown still
+        // kills the process
+        restore_defaults();
+    }
+}
+
+fn restore_defaults() {
+    for signal in HANDLED {
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+    }
+    WRITE_FD.store(-1, Ordering::SeqCst);
+}
+
+/// Signal context: only async-signal-safe calls, and errno left as found
+extern "C" fn on_signal(signal: libc::c_int) {
+    let fd = WRITE_FD.load(Ordering::SeqCst);
+    if fd < 0 {
+        return;
+    }
+    unsafe {
+        let errno = *libc::__errno_location();
+        let byte = signal as u8;
+        // A full pipe already holds plenty of unread signals; drop this one
+        libc::write(fd, &byte as *const u8 as *const libc::c_void, 1);
+        *libc::__errno_location() = errno;
+    }
+}
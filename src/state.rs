@@ -0,0 +1,163 @@
+//! Runtime state shared between the main loop and control interfaces
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+use crate::config::DEFAULT_PROFILE;
+use crate::focus_detector::FocusState;
+use crate::game_state::GamePhase;
+use crate::stats::SessionStats;
+use crate::supervisor::Health;
+
+/// Handles to the daemon's live state
+///
+/// Cheap to clone — every field is shared, so the main loop, the hotkey and
+/// the control socket all see (and change) the same values.
+#[derive(Clone)]
+pub struct DaemonState {
+    enabled: Arc<AtomicBool>,
+    delay_ms: Arc<AtomicU64>,
+    game_phases: Arc<Mutex<Vec<GamePhase>>>,
+    reload_requested: Arc<AtomicBool>,
+    stats: Arc<SessionStats>,
+    profile: Arc<Mutex<String>>,
+    profile_names: Arc<Mutex<Vec<String>>>,
+    requested_profile: Arc<Mutex<Option<String>>>,
+    focus: Arc<FocusState>,
+    health: Health,
+}
+
+impl DaemonState {
+    pub fn new(focus: Arc<FocusState>, delay_ms: u64) -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(true)),
+            delay_ms: Arc::new(AtomicU64::new(delay_ms)),
+            game_phases: Arc::new(Mutex::new(Vec::new())),
+            reload_requested: Arc::new(AtomicBool::new(false)),
+            stats: Arc::new(SessionStats::new()),
+            profile: Arc::new(Mutex::new(DEFAULT_PROFILE.to_string())),
+            profile_names: Arc::new(Mutex::new(vec![DEFAULT_PROFILE.to_string()])),
+            requested_profile: Arc::new(Mutex::new(None)),
+            focus,
+            health: Health::new(),
+        }
+    }
+
+    /// Whether auto-clicking is enabled (independent of focus)
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Enable or disable auto-clicking, logging the change and its source
+    pub fn set_enabled(&self, enabled: bool, source: &str) {
+        let previous = self.enabled.swap(enabled, Ordering::SeqCst);
+        if previous != enabled {
+            info!("Auto-click {} ({})", enabled_label(enabled), source);
+        }
+    }
+
+    /// Flip the enabled flag, returning the new value
+    pub fn toggle_enabled(&self, source: &str) -> bool {
+        let enabled = !self.enabled.fetch_xor(true, Ordering::SeqCst);
+        info!("Auto-click {} ({})", enabled_label(enabled), source);
+        enabled
+    }
+
+    /// Whether the target window is currently focused
+    pub fn is_focused(&self) -> bool {
+        self.focus.is_focused()
+    }
+
+    /// Shared focus state
+    pub fn focus(&self) -> &Arc<FocusState> {
+        &self.focus
+    }
+
+    /// Current auto-click delay in milliseconds
+    pub fn delay_ms(&self) -> u64 {
+        self.delay_ms.load(Ordering::SeqCst)
+    }
+
+    pub fn set_delay_ms(&self, delay_ms: u64) {
+        let previous = self.delay_ms.swap(delay_ms, Ordering::SeqCst);
+        if previous != delay_ms {
+            info!("Auto-click delay set to {}ms", delay_ms);
+        }
+    }
+
+    /// Game phases auto-clicks are allowed in (empty = any)
+    pub fn game_phases(&self) -> Vec<GamePhase> {
+        self.game_phases.lock().unwrap().clone()
+    }
+
+    pub fn set_game_phases(&self, game_phases: Vec<GamePhase>) {
+        *self.game_phases.lock().unwrap() = game_phases;
+    }
+
+    /// Auto-clicks sent this session
+    pub fn click_count(&self) -> u64 {
+        self.stats.auto_clicks()
+    }
+
+    /// Session counters and latency histogram
+    pub fn stats(&self) -> &Arc<SessionStats> {
+        &self.stats
+    }
+
+    /// Health of the supervised workers
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    /// Name of the active profile
+    pub fn profile(&self) -> String {
+        self.profile.lock().unwrap().clone()
+    }
+
+    /// Record the profile the main loop has applied
+    pub fn set_profile(&self, name: &str) {
+        let mut profile = self.profile.lock().unwrap();
+        if *profile != name {
+            info!("Profile: {}", name);
+            *profile = name.to_string();
+        }
+    }
+
+    /// Profiles in the config file, as of the last (re)load
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profile_names.lock().unwrap().clone()
+    }
+
+    pub fn set_profile_names(&self, names: Vec<String>) {
+        *self.profile_names.lock().unwrap() = names;
+    }
+
+    /// Ask the main loop to switch to another profile
+    pub fn request_profile(&self, name: &str) {
+        *self.requested_profile.lock().unwrap() = Some(name.to_string());
+    }
+
+    /// Consume a pending profile switch
+    pub fn take_profile_request(&self) -> Option<String> {
+        self.requested_profile.lock().unwrap().take()
+    }
+
+    /// Ask the main loop to reload the config file
+    pub fn request_reload(&self) {
+        self.reload_requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Consume a pending reload request
+    pub fn take_reload_request(&self) -> bool {
+        self.reload_requested.swap(false, Ordering::SeqCst)
+    }
+}
+
+fn enabled_label(enabled: bool) -> &'static str {
+    if enabled {
+        "enabled"
+    } else {
+        "disabled"
+    }
+}
@@ -0,0 +1,333 @@
+//! Session statistics: decision counters and trigger-to-emit latency
+//!
+//! Everything is atomic so the listener thread, the main loop and the
+//! control interfaces can update and read the counters without locking.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds (µs) of the latency histogram buckets; one extra bucket
+/// catches everything slower
+const LATENCY_BUCKETS_US: &[u64] = &[
+    5_000, 10_000, 12_000, 14_000, 15_000, 16_000, 17_000, 18_000, 20_000, 25_000, 30_000,
+    50_000, 100_000,
+];
+
+/// Why a trigger did not produce an auto-click
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuppressReason {
+    /// Auto-clicking was paused
+    Disabled,
+    /// The game window wasn't focused (or the pointer was outside it)
+    Focus,
+    /// The cursor or game phase gate blocked it
+    Gate,
+    /// The release was the echo of our own auto-click
+    Feedback,
+    /// A real click came too soon after our auto-click
+    RateLimit,
+}
+
+/// Histogram of trigger-to-emit latencies
+pub struct LatencyHistogram {
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum_us: AtomicU64,
+    min_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            buckets: (0..=LATENCY_BUCKETS_US.len()).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum_us: AtomicU64::new(0),
+            min_us: AtomicU64::new(u64::MAX),
+            max_us: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, latency: Duration) {
+        let us = latency.as_micros().min(u64::MAX as u128) as u64;
+        let index = LATENCY_BUCKETS_US
+            .iter()
+            .position(|&bound| us < bound)
+            .unwrap_or(LATENCY_BUCKETS_US.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+        self.min_us.fetch_min(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> LatencySnapshot {
+        let count = self.count.load(Ordering::Relaxed);
+        let buckets = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| LatencyBucket {
+                upper_us: LATENCY_BUCKETS_US.get(i).copied(),
+                count: bucket.load(Ordering::Relaxed),
+            })
+            .collect();
+
+        LatencySnapshot {
+            count,
+            min_us: if count == 0 { 0 } else { self.min_us.load(Ordering::Relaxed) },
+            max_us: self.max_us.load(Ordering::Relaxed),
+            mean_us: self.sum_us.load(Ordering::Relaxed).checked_div(count).unwrap_or(0),
+            buckets,
+        }
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counters for one daemon session
+#[derive(Default)]
+pub struct SessionStats {
+    triggers: AtomicU64,
+    auto_clicks: AtomicU64,
+    suppressed_disabled: AtomicU64,
+    suppressed_focus: AtomicU64,
+    suppressed_gate: AtomicU64,
+    suppressed_feedback: AtomicU64,
+    suppressed_rate_limit: AtomicU64,
+    send_failures: AtomicU64,
+    natural_double_taps: AtomicU64,
+    latency: LatencyHistogram,
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A trigger release was seen, before any filtering
+    pub fn record_trigger(&self) {
+        self.triggers.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An auto-click was sent, `latency` after the trigger was seen
+    pub fn record_auto_click(&self, latency: Duration) {
+        self.auto_clicks.fetch_add(1, Ordering::Relaxed);
+        self.latency.record(latency);
+    }
+
+    /// A trigger was dropped instead of producing an auto-click
+    pub fn record_suppressed(&self, reason: SuppressReason) {
+        let counter = match reason {
+            SuppressReason::Disabled => &self.suppressed_disabled,
+            SuppressReason::Focus => &self.suppressed_focus,
+            SuppressReason::Gate => &self.suppressed_gate,
+            SuppressReason::Feedback => &self.suppressed_feedback,
+            SuppressReason::RateLimit => &self.suppressed_rate_limit,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Writing the auto-click to the virtual device failed
+    pub fn record_send_failure(&self) {
+        self.send_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The user released the trigger twice in quick succession themselves
+    pub fn record_natural_double_tap(&self) {
+        self.natural_double_taps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Auto-clicks sent so far
+    pub fn auto_clicks(&self) -> u64 {
+        self.auto_clicks.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            triggers: self.triggers.load(Ordering::Relaxed),
+            auto_clicks: self.auto_clicks(),
+            suppressed_disabled: self.suppressed_disabled.load(Ordering::Relaxed),
+            suppressed_focus: self.suppressed_focus.load(Ordering::Relaxed),
+            suppressed_gate: self.suppressed_gate.load(Ordering::Relaxed),
+            suppressed_feedback: self.suppressed_feedback.load(Ordering::Relaxed),
+            suppressed_rate_limit: self.suppressed_rate_limit.load(Ordering::Relaxed),
+            send_failures: self.send_failures.load(Ordering::Relaxed),
+            natural_double_taps: self.natural_double_taps.load(Ordering::Relaxed),
+            latency: self.latency.snapshot(),
+        }
+    }
+}
+
+/// One histogram bucket; `upper_us` is None for the overflow bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyBucket {
+    pub upper_us: Option<u64>,
+    pub count: u64,
+}
+
+/// Point-in-time copy of the latency histogram
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencySnapshot {
+    pub count: u64,
+    pub min_us: u64,
+    pub max_us: u64,
+    pub mean_us: u64,
+    pub buckets: Vec<LatencyBucket>,
+}
+
+/// Point-in-time copy of the session counters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub triggers: u64,
+    pub auto_clicks: u64,
+    pub suppressed_disabled: u64,
+    pub suppressed_focus: u64,
+    pub suppressed_gate: u64,
+    pub suppressed_feedback: u64,
+    pub suppressed_rate_limit: u64,
+    pub send_failures: u64,
+    pub natural_double_taps: u64,
+    pub latency: LatencySnapshot,
+}
+
+impl fmt::Display for StatsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "triggers seen:        {}", self.triggers)?;
+        writeln!(f, "auto-clicks sent:     {}", self.auto_clicks)?;
+        writeln!(f, "suppressed, disabled: {}", self.suppressed_disabled)?;
+        writeln!(f, "suppressed, focus:    {}", self.suppressed_focus)?;
+        writeln!(f, "suppressed, gates:    {}", self.suppressed_gate)?;
+        writeln!(f, "filtered, feedback:   {}", self.suppressed_feedback)?;
+        writeln!(f, "filtered, rate limit: {}", self.suppressed_rate_limit)?;
+        writeln!(f, "send failures:        {}", self.send_failures)?;
+        writeln!(f, "natural double-taps:  {}", self.natural_double_taps)?;
+
+        let latency = &self.latency;
+        if latency.count == 0 {
+            return write!(f, "latency:              no samples");
+        }
+        write!(
+            f,
+            "latency (trigger → emit): min {:.1}ms, mean {:.1}ms, max {:.1}ms",
+            latency.min_us as f64 / 1000.0,
+            latency.mean_us as f64 / 1000.0,
+            latency.max_us as f64 / 1000.0,
+        )?;
+
+        let mut lower_us = 0;
+        for bucket in latency.buckets.iter() {
+            if bucket.count > 0 {
+                match bucket.upper_us {
+                    Some(upper_us) => write!(
+                        f,
+                        "\n  {:>5.1}–{:<5.1}ms  {}",
+                        lower_us as f64 / 1000.0,
+                        upper_us as f64 / 1000.0,
+                        bucket.count
+                    )?,
+                    None => write!(
+                        f,
+                        "\n  {:>5.1}+ ms        {}",
+                        lower_us as f64 / 1000.0,
+                        bucket.count
+                    )?,
+                }
+            }
+            lower_us = bucket.upper_us.unwrap_or(lower_us);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket_counts(snapshot: &LatencySnapshot) -> Vec<u64> {
+        snapshot.buckets.iter().map(|bucket| bucket.count).collect()
+    }
+
+    #[test]
+    fn latencies_land_in_the_first_bucket_they_are_below() {
+        let histogram = LatencyHistogram::new();
+        histogram.record(Duration::from_micros(4_999));
+        histogram.record(Duration::from_micros(5_000));
+        histogram.record(Duration::from_micros(14_999));
+        histogram.record(Duration::from_micros(15_000));
+
+        let counts = bucket_counts(&histogram.snapshot());
+        assert_eq!(&counts[..5], [1, 1, 0, 0, 1]);
+        assert_eq!(counts[5], 1, "15.0ms belongs to the 15–16ms bucket");
+        assert_eq!(counts.iter().sum::<u64>(), 4);
+    }
+
+    #[test]
+    fn slow_latencies_go_to_the_overflow_bucket() {
+        let histogram = LatencyHistogram::new();
+        histogram.record(Duration::from_micros(99_999));
+        histogram.record(Duration::from_micros(100_000));
+        histogram.record(Duration::from_secs(u64::MAX));
+
+        let snapshot = histogram.snapshot();
+        let overflow = snapshot.buckets.last().unwrap();
+        assert_eq!(snapshot.buckets.len(), LATENCY_BUCKETS_US.len() + 1);
+        assert_eq!(overflow.upper_us, None);
+        assert_eq!(overflow.count, 2);
+        assert_eq!(snapshot.buckets[snapshot.buckets.len() - 2].count, 1);
+        assert_eq!(snapshot.max_us, u64::MAX);
+    }
+
+    #[test]
+    fn an_empty_histogram_reports_zeros() {
+        let snapshot = LatencyHistogram::new().snapshot();
+        assert_eq!(snapshot.count, 0);
+        assert_eq!(snapshot.min_us, 0);
+        assert_eq!(snapshot.max_us, 0);
+        assert_eq!(snapshot.mean_us, 0);
+    }
+
+    #[test]
+    fn min_max_and_mean_follow_the_samples() {
+        let histogram = LatencyHistogram::new();
+        for us in [14_000, 15_000, 16_500] {
+            histogram.record(Duration::from_micros(us));
+        }
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 3);
+        assert_eq!(snapshot.min_us, 14_000);
+        assert_eq!(snapshot.max_us, 16_500);
+        assert_eq!(snapshot.mean_us, 15_166);
+    }
+
+    #[test]
+    fn display_lists_counters_and_non_empty_buckets() {
+        let stats = SessionStats::new();
+        for _ in 0..3 {
+            stats.record_trigger();
+        }
+        stats.record_auto_click(Duration::from_micros(15_200));
+        stats.record_auto_click(Duration::from_micros(150_000));
+        stats.record_suppressed(SuppressReason::RateLimit);
+
+        let text = stats.snapshot().to_string();
+        assert!(text.starts_with("triggers seen:        3\nauto-clicks sent:     2\n"));
+        assert!(text.contains("filtered, rate limit: 1\n"));
+        assert!(text.contains("latency (trigger → emit): min 15.2ms, mean 82.6ms, max 150.0ms"));
+        assert!(text.ends_with("\n   15.0–16.0 ms  1\n  100.0+ ms        1"), "{}", text);
+    }
+
+    #[test]
+    fn display_without_samples_says_so() {
+        let text = SessionStats::new().snapshot().to_string();
+        assert!(text.ends_with("natural double-taps:  0\nlatency:              no samples"));
+    }
+}
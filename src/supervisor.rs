@@ -0,0 +1,386 @@
+//! Restarting background workers that die
+//!
+//! The listener, focus poller and trigger pipeline run as supervised
+//! workers: if one returns or panics while the engine is still running, it
+//! is started again after a growing backoff. A worker that keeps dying —
+//! more than [`MAX_FAILURES`] times without a stable run in between — is
+//! marked failed and the engine stops. A mouse going away doesn't count:
+//! the listener keeps running and picks it up again. Each worker's health
+//! is shared with status queries.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::engine::{Clock, SystemClock};
+use crate::shutdown::{Workers, SHUTDOWN_TIMEOUT};
+
+/// Restarts allowed in a row before giving up
+pub const MAX_FAILURES: u32 = 5;
+
+/// Wait before the first restart; doubles with each further failure
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// A worker that ran this long before dying starts counting failures afresh
+const STABLE_AFTER: Duration = Duration::from_secs(30);
+
+/// How often the workers are checked
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What a supervised worker is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerState {
+    Running,
+    /// Died, waiting for the backoff before starting again
+    Restarting,
+    /// Died too often; the engine stopped
+    Failed,
+}
+
+/// Health of one supervised worker
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerHealth {
+    pub name: String,
+    pub state: WorkerState,
+    /// Restarts so far this session
+    pub restarts: u32,
+    /// How the worker last died
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl fmt::Display for WorkerHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            WorkerState::Running => "running",
+            WorkerState::Restarting => "restarting",
+            WorkerState::Failed => "failed",
+        };
+        write!(f, "{}: {}", self.name, state)?;
+        if self.restarts > 0 {
+            write!(f, ", {} restart(s)", self.restarts)?;
+        }
+        if let Some(error) = &self.last_error {
+            write!(f, ", last: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Health of every supervised worker, shared with status queries
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    workers: Arc<Mutex<Vec<WorkerHealth>>>,
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn workers(&self) -> Vec<WorkerHealth> {
+        self.workers.lock().unwrap().clone()
+    }
+
+    /// Why the engine gave up, if a worker failed for good
+    pub fn failure(&self) -> Option<String> {
+        self.workers
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|worker| worker.state == WorkerState::Failed)
+            .map(|worker| {
+                format!(
+                    "{} died {} times in a row (last: {})",
+                    worker.name,
+                    MAX_FAILURES + 1,
+                    worker.last_error.as_deref().unwrap_or("exited")
+                )
+            })
+    }
+
+    fn update(&self, health: WorkerHealth) {
+        let mut workers = self.workers.lock().unwrap();
+        match workers.iter_mut().find(|worker| worker.name == health.name) {
+            Some(worker) => *worker = health,
+            None => workers.push(health),
+        }
+    }
+}
+
+type Spawn = Box<dyn FnMut() -> thread::JoinHandle<()> + Send>;
+
+struct Worker {
+    name: &'static str,
+    /// None for workers that are only joined, never restarted
+    spawn: Option<Spawn>,
+    handle: Option<thread::JoinHandle<()>>,
+    started: Instant,
+    failures: u32,
+    restart_at: Option<Instant>,
+    health: WorkerHealth,
+}
+
+/// Watches workers, restarting the supervised ones when they die
+pub struct Supervisor {
+    workers: Vec<Worker>,
+    health: Health,
+    clock: Arc<dyn Clock>,
+}
+
+impl Supervisor {
+    pub fn new(health: Health) -> Self {
+        Self {
+            workers: Vec::new(),
+            health,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Time backoffs and stable runs on `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Start a worker now, and again whenever it dies
+    pub fn supervise(
+        &mut self,
+        name: &'static str,
+        mut spawn: impl FnMut() -> thread::JoinHandle<()> + Send + 'static,
+    ) {
+        let handle = spawn();
+        self.push(name, Some(Box::new(spawn)), handle);
+    }
+
+    /// Join a worker at shutdown without restarting it
+    pub fn watch(&mut self, name: &'static str, handle: thread::JoinHandle<()>) {
+        self.push(name, None, handle);
+    }
+
+    fn push(&mut self, name: &'static str, spawn: Option<Spawn>, handle: thread::JoinHandle<()>) {
+        let worker = Worker {
+            name,
+            spawn,
+            handle: Some(handle),
+            started: self.clock.now(),
+            failures: 0,
+            restart_at: None,
+            health: WorkerHealth {
+                name: name.to_string(),
+                state: WorkerState::Running,
+                restarts: 0,
+                last_error: None,
+            },
+        };
+        if worker.spawn.is_some() {
+            self.health.update(worker.health.clone());
+        }
+        self.workers.push(worker);
+    }
+
+    /// Watch the workers until `running` is cleared — by the caller, or
+    /// here when a worker fails for good — then join them, latest first
+    pub fn start(mut self, running: Arc<AtomicBool>) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            while running.load(Ordering::SeqCst) {
+                self.poll(&running);
+                thread::sleep(CHECK_INTERVAL);
+            }
+
+            let mut workers = Workers::new();
+            for worker in self.workers.into_iter().rev() {
+                if let Some(handle) = worker.handle {
+                    workers.add(worker.name, handle);
+                }
+            }
+            workers.join_until(Instant::now() + SHUTDOWN_TIMEOUT);
+        })
+    }
+
+    /// One pass over the workers
+    fn poll(&mut self, running: &AtomicBool) {
+        let now = self.clock.now();
+        for worker in self.workers.iter_mut() {
+            check(worker, &self.health, running, now);
+        }
+    }
+}
+
+/// Notice a dead worker, or restart it once its backoff is over
+fn check(worker: &mut Worker, health: &Health, running: &AtomicBool, now: Instant) {
+    let Some(spawn) = worker.spawn.as_mut() else {
+        return;
+    };
+
+    if worker.handle.as_ref().is_some_and(|handle| handle.is_finished()) {
+        // Finishing because we're stopping isn't dying
+        if !running.load(Ordering::SeqCst) {
+            return;
+        }
+        let error = match worker.handle.take().map(|handle| handle.join()) {
+            Some(Err(panic)) => format!("panicked: {}", panic_message(&*panic)),
+            _ => "exited".to_string(),
+        };
+
+        if now.duration_since(worker.started) >= STABLE_AFTER {
+            worker.failures = 0;
+        }
+        worker.failures += 1;
+        worker.health.last_error = Some(error.clone());
+
+        if worker.failures > MAX_FAILURES {
+            error!("The {} keeps dying ({}), giving up", worker.name, error);
+            worker.health.state = WorkerState::Failed;
+            health.update(worker.health.clone());
+            running.store(false, Ordering::SeqCst);
+            return;
+        }
+
+        let backoff = (INITIAL_BACKOFF * 2u32.pow(worker.failures - 1)).min(MAX_BACKOFF);
+        warn!("The {} stopped ({}), restarting in {:?}", worker.name, error, backoff);
+        worker.restart_at = Some(now + backoff);
+        worker.health.state = WorkerState::Restarting;
+        health.update(worker.health.clone());
+    } else if worker.restart_at.is_some_and(|at| now >= at) {
+        worker.restart_at = None;
+        worker.handle = Some(spawn());
+        worker.started = now;
+        worker.health.restarts += 1;
+        worker.health.state = WorkerState::Running;
+        health.update(worker.health.clone());
+        info!("Restarted the {}", worker.name);
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::VirtualClock;
+    use std::sync::atomic::AtomicU32;
+
+    /// A supervisor with one worker that dies as soon as it starts,
+    /// panicking, and counts its starts
+    fn dying_worker() -> (Supervisor, Arc<VirtualClock>, Arc<AtomicU32>) {
+        let clock = Arc::new(VirtualClock::new());
+        let starts = Arc::new(AtomicU32::new(0));
+        let mut supervisor = Supervisor::new(Health::new()).with_clock(clock.clone());
+        let counter = starts.clone();
+        supervisor.supervise("test worker", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(|| panic!("boom"))
+        });
+        (supervisor, clock, starts)
+    }
+
+    /// Wait for the worker's thread to finish, then poll once
+    fn poll_after_exit(supervisor: &mut Supervisor, running: &AtomicBool) {
+        while supervisor.workers[0]
+            .handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+        {
+            thread::sleep(Duration::from_millis(1));
+        }
+        supervisor.poll(running);
+    }
+
+    fn state(supervisor: &Supervisor) -> WorkerState {
+        supervisor.health.workers()[0].state
+    }
+
+    #[test]
+    fn backoff_doubles_and_restarts_at_the_deadline() {
+        let (mut supervisor, clock, starts) = dying_worker();
+        let running = AtomicBool::new(true);
+
+        for backoff in [250, 500, 1000] {
+            poll_after_exit(&mut supervisor, &running);
+            assert_eq!(state(&supervisor), WorkerState::Restarting);
+            let restarts = starts.load(Ordering::SeqCst);
+
+            clock.advance(Duration::from_millis(backoff - 1));
+            supervisor.poll(&running);
+            assert_eq!(starts.load(Ordering::SeqCst), restarts);
+
+            clock.advance(Duration::from_millis(1));
+            supervisor.poll(&running);
+            assert_eq!(starts.load(Ordering::SeqCst), restarts + 1);
+            assert_eq!(state(&supervisor), WorkerState::Running);
+        }
+
+        let health = &supervisor.health.workers()[0];
+        assert_eq!(health.restarts, 3);
+        assert_eq!(health.last_error.as_deref(), Some("panicked: boom"));
+    }
+
+    #[test]
+    fn a_stable_run_resets_the_failures() {
+        let (mut supervisor, clock, _) = dying_worker();
+        let running = AtomicBool::new(true);
+
+        for _ in 0..3 {
+            poll_after_exit(&mut supervisor, &running);
+            clock.advance(MAX_BACKOFF);
+            supervisor.poll(&running);
+        }
+        assert_eq!(supervisor.workers[0].failures, 3);
+
+        clock.advance(STABLE_AFTER);
+        poll_after_exit(&mut supervisor, &running);
+        assert_eq!(supervisor.workers[0].failures, 1);
+        assert_eq!(supervisor.workers[0].restart_at, Some(clock.now() + INITIAL_BACKOFF));
+    }
+
+    #[test]
+    fn too_many_failures_stop_the_engine() {
+        let (mut supervisor, clock, starts) = dying_worker();
+        let running = AtomicBool::new(true);
+
+        for _ in 0..MAX_FAILURES {
+            poll_after_exit(&mut supervisor, &running);
+            clock.advance(MAX_BACKOFF);
+            supervisor.poll(&running);
+        }
+        assert!(supervisor.health.failure().is_none());
+
+        poll_after_exit(&mut supervisor, &running);
+        assert_eq!(state(&supervisor), WorkerState::Failed);
+        assert!(!running.load(Ordering::SeqCst));
+        assert_eq!(
+            supervisor.health.failure().as_deref(),
+            Some("test worker died 6 times in a row (last: panicked: boom)")
+        );
+
+        clock.advance(MAX_BACKOFF);
+        supervisor.poll(&running);
+        assert_eq!(starts.load(Ordering::SeqCst), MAX_FAILURES + 1);
+    }
+
+    #[test]
+    fn stopping_is_not_dying() {
+        let clock = Arc::new(VirtualClock::new());
+        let mut supervisor = Supervisor::new(Health::new()).with_clock(clock.clone());
+        supervisor.supervise("test worker", || thread::spawn(|| {}));
+        let running = AtomicBool::new(false);
+
+        poll_after_exit(&mut supervisor, &running);
+        assert_eq!(state(&supervisor), WorkerState::Running);
+        assert_eq!(supervisor.workers[0].failures, 0);
+    }
+}
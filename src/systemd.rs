@@ -0,0 +1,256 @@
+//! systemd readiness and watchdog notifications
+//!
+//! When started by a `Type=notify` unit, systemd passes a datagram socket
+//! in `NOTIFY_SOCKET`. The daemon sends `READY=1` once the virtual device
+//! exists, `STATUS=` lines as auto-clicking and focus change, `WATCHDOG=1`
+//! from its main loop when the unit sets `WatchdogSec=`, and `STOPPING=1`
+//! on shutdown. Outside systemd none of this does anything.
+
+use std::env;
+use std::io;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Sends notifications to the service manager
+#[derive(Debug)]
+pub struct SystemdNotifier {
+    socket: UnixDatagram,
+    address: SocketAddr,
+    /// How often to ping the watchdog (half its timeout), if enabled
+    watchdog: Option<Duration>,
+    last_ping: Option<Instant>,
+    last_status: Option<String>,
+}
+
+impl SystemdNotifier {
+    /// Notifier for the socket systemd passed in the environment, if any
+    pub fn from_env() -> Option<Self> {
+        let path = env::var_os("NOTIFY_SOCKET")?;
+        let mut notifier = match Self::connect(Path::new(&path)) {
+            Ok(notifier) => notifier,
+            Err(e) => {
+                warn!("Ignoring NOTIFY_SOCKET {:?}: {}", path, e);
+                return None;
+            }
+        };
+        if let Some(timeout) = watchdog_timeout() {
+            notifier = notifier.with_watchdog(timeout / 2);
+        }
+        Some(notifier)
+    }
+
+    /// Notifier for the socket at `path`; a leading `@` names an abstract
+    /// socket
+    pub fn connect(path: &Path) -> io::Result<Self> {
+        let address = match path.to_str().and_then(|path| path.strip_prefix('@')) {
+            Some(name) => abstract_address(name)?,
+            None => SocketAddr::from_pathname(path)?,
+        };
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            address,
+            watchdog: None,
+            last_ping: None,
+            last_status: None,
+        })
+    }
+
+    /// Ping the watchdog every `interval` from [`watchdog`](Self::watchdog)
+    pub fn with_watchdog(mut self, interval: Duration) -> Self {
+        self.watchdog = Some(interval);
+        self
+    }
+
+    /// Send raw `KEY=value` lines
+    pub fn notify(&self, message: &str) -> io::Result<()> {
+        self.socket.send_to_addr(message.as_bytes(), &self.address)?;
+        debug!("Sent {:?} to the service manager", message);
+        Ok(())
+    }
+
+    /// The daemon is up and auto-clicks can be sent
+    pub fn ready(&self) {
+        self.send("READY=1");
+    }
+
+    /// Show `status` in `systemctl status`; repeats are skipped
+    pub fn status(&mut self, status: &str) {
+        if self.last_status.as_deref() != Some(status) {
+            self.send(&format!("STATUS={}", status));
+            self.last_status = Some(status.to_string());
+        }
+    }
+
+    /// Ping the watchdog if it's enabled and due; call from the main loop
+    pub fn watchdog(&mut self) {
+        let Some(interval) = self.watchdog else {
+            return;
+        };
+        if self.last_ping.is_none_or(|last| last.elapsed() >= interval) {
+            self.send("WATCHDOG=1");
+            self.last_ping = Some(Instant::now());
+        }
+    }
+
+    /// The daemon is shutting down
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    fn send(&self, message: &str) {
+        if let Err(e) = self.notify(message) {
+            warn!("Failed to notify the service manager: {}", e);
+        }
+    }
+}
+
+/// `WATCHDOG_USEC`, if it is meant for this process
+fn watchdog_timeout() -> Option<Duration> {
+    parse_watchdog(
+        &env::var("WATCHDOG_USEC").ok()?,
+        env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+/// The watchdog timeout from `WATCHDOG_USEC` and `WATCHDOG_PID`, unless
+/// the latter names a process other than `pid`
+fn parse_watchdog(usec: &str, watchdog_pid: Option<&str>, pid: u32) -> Option<Duration> {
+    let usec: u64 = usec.parse().ok()?;
+    if let Some(watchdog_pid) = watchdog_pid {
+        if watchdog_pid.parse() != Ok(pid) {
+            return None;
+        }
+    }
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+fn abstract_address(name: &str) -> io::Result<SocketAddr> {
+    use std::os::linux::net::SocketAddrExt;
+    SocketAddr::from_abstract_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::thread;
+
+    /// A bound socket standing in for systemd's, removed on drop
+    struct Manager {
+        socket: UnixDatagram,
+        dir: Option<PathBuf>,
+    }
+
+    impl Manager {
+        fn bind(name: &str) -> (Self, PathBuf) {
+            let dir = env::temp_dir().join(format!("doubletap-rl-test-{}-{}", std::process::id(), name));
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("notify");
+            let _ = fs::remove_file(&path);
+            let socket = UnixDatagram::bind(&path).unwrap();
+            socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+            (Self { socket, dir: Some(dir) }, path)
+        }
+
+        fn bind_abstract(name: &str) -> (Self, PathBuf) {
+            let name = format!("doubletap-rl-test-{}-{}", std::process::id(), name);
+            let socket = UnixDatagram::bind_addr(&abstract_address(&name).unwrap()).unwrap();
+            socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+            (Self { socket, dir: None }, PathBuf::from(format!("@{}", name)))
+        }
+
+        fn recv(&self) -> String {
+            let mut buf = [0; 256];
+            let len = self.socket.recv(&mut buf).unwrap();
+            String::from_utf8(buf[..len].to_vec()).unwrap()
+        }
+
+        /// Every datagram already sent
+        fn drain(&self) -> Vec<String> {
+            self.socket.set_nonblocking(true).unwrap();
+            let mut messages = Vec::new();
+            let mut buf = [0; 256];
+            while let Ok(len) = self.socket.recv(&mut buf) {
+                messages.push(String::from_utf8(buf[..len].to_vec()).unwrap());
+            }
+            self.socket.set_nonblocking(false).unwrap();
+            messages
+        }
+    }
+
+    impl Drop for Manager {
+        fn drop(&mut self) {
+            if let Some(dir) = &self.dir {
+                let _ = fs::remove_dir_all(dir);
+            }
+        }
+    }
+
+    #[test]
+    fn sends_the_lifecycle_to_a_path_socket() {
+        let (manager, path) = Manager::bind("lifecycle");
+        let mut notifier = SystemdNotifier::connect(&path).unwrap();
+
+        notifier.ready();
+        notifier.status("Auto-clicking");
+        notifier.status("Auto-clicking");
+        notifier.status("Paused");
+        notifier.stopping();
+
+        assert_eq!(manager.recv(), "READY=1");
+        assert_eq!(manager.recv(), "STATUS=Auto-clicking");
+        assert_eq!(manager.recv(), "STATUS=Paused");
+        assert_eq!(manager.recv(), "STOPPING=1");
+        assert!(manager.drain().is_empty());
+    }
+
+    #[test]
+    fn sends_to_an_abstract_socket() {
+        let (manager, path) = Manager::bind_abstract("abstract");
+        let notifier = SystemdNotifier::connect(&path).unwrap();
+
+        notifier.ready();
+
+        assert_eq!(manager.recv(), "READY=1");
+    }
+
+    #[test]
+    fn watchdog_pings_once_per_interval() {
+        let (manager, path) = Manager::bind("watchdog");
+        let interval = Duration::from_millis(100);
+        let mut notifier = SystemdNotifier::connect(&path).unwrap().with_watchdog(interval);
+
+        notifier.watchdog();
+        notifier.watchdog();
+        assert_eq!(manager.drain(), ["WATCHDOG=1"]);
+
+        thread::sleep(interval);
+        notifier.watchdog();
+        notifier.watchdog();
+        assert_eq!(manager.drain(), ["WATCHDOG=1"]);
+    }
+
+    #[test]
+    fn watchdog_is_off_by_default() {
+        let (manager, path) = Manager::bind("no-watchdog");
+        let mut notifier = SystemdNotifier::connect(&path).unwrap();
+
+        notifier.watchdog();
+
+        assert!(manager.drain().is_empty());
+    }
+
+    #[test]
+    fn watchdog_timeout_is_for_this_process_only() {
+        assert_eq!(parse_watchdog("10000000", None, 42), Some(Duration::from_secs(10)));
+        assert_eq!(parse_watchdog("10000000", Some("42"), 42), Some(Duration::from_secs(10)));
+        assert_eq!(parse_watchdog("10000000", Some("43"), 42), None);
+        assert_eq!(parse_watchdog("10000000", Some("not a pid"), 42), None);
+        assert_eq!(parse_watchdog("0", None, 42), None);
+        assert_eq!(parse_watchdog("ten seconds", None, 42), None);
+    }
+}
@@ -0,0 +1,179 @@
+//! StatusNotifierItem tray icon
+//!
+//! Shows whether auto-clicking is disabled, enabled, or active (enabled and
+//! the game focused), with a menu to toggle, switch profile and quit.
+
+use ksni::blocking::TrayMethods;
+use ksni::menu::{CheckmarkItem, RadioGroup, RadioItem, StandardItem, SubMenu};
+use ksni::{MenuItem, Status, ToolTip};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::info;
+
+use crate::state::DaemonState;
+use crate::DoubleTapError;
+
+/// How often the icon is refreshed from the daemon state
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What the icon currently shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Snapshot {
+    enabled: bool,
+    focused: bool,
+    profile: String,
+    profiles: Vec<String>,
+}
+
+impl Snapshot {
+    fn from_state(state: &DaemonState) -> Self {
+        Self {
+            enabled: state.is_enabled(),
+            focused: state.is_focused(),
+            profile: state.profile(),
+            profiles: state.profile_names(),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match (self.enabled, self.focused) {
+            (false, _) => "Disabled",
+            (true, false) => "Enabled — waiting for Rocket League",
+            (true, true) => "Active — Rocket League focused",
+        }
+    }
+}
+
+struct DoubleTapTray {
+    state: DaemonState,
+    running: Arc<AtomicBool>,
+}
+
+impl ksni::Tray for DoubleTapTray {
+    fn id(&self) -> String {
+        env!("CARGO_PKG_NAME").into()
+    }
+
+    fn title(&self) -> String {
+        "DoubleTap-RL".into()
+    }
+
+    fn status(&self) -> Status {
+        if Snapshot::from_state(&self.state).focused {
+            Status::Active
+        } else {
+            Status::Passive
+        }
+    }
+
+    fn icon_name(&self) -> String {
+        let snapshot = Snapshot::from_state(&self.state);
+        match (snapshot.enabled, snapshot.focused) {
+            (false, _) => "media-playback-stop",
+            (true, false) => "media-playback-pause",
+            (true, true) => "media-playback-start",
+        }
+        .into()
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        let snapshot = Snapshot::from_state(&self.state);
+        ToolTip {
+            title: "DoubleTap-RL".into(),
+            description: format!("{} (profile: {})", snapshot.description(), snapshot.profile),
+            ..Default::default()
+        }
+    }
+
+    /// Left click toggles auto-clicking
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.state.toggle_enabled("tray");
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let profile = self.state.profile();
+        let profiles = self.state.profile_names();
+        let mut menu = vec![CheckmarkItem {
+            label: "Enabled".into(),
+            checked: self.state.is_enabled(),
+            activate: Box::new(|tray: &mut Self| {
+                tray.state.toggle_enabled("tray");
+            }),
+            ..Default::default()
+        }
+        .into()];
+
+        if profiles.len() > 1 {
+            menu.push(
+                SubMenu {
+                    label: "Profile".into(),
+                    submenu: vec![RadioGroup {
+                        selected: profiles.iter().position(|p| *p == profile).unwrap_or(0),
+                        select: Box::new(|tray: &mut Self, index| {
+                            if let Some(name) = tray.state.profile_names().get(index) {
+                                tray.state.request_profile(name);
+                            }
+                        }),
+                        options: profiles
+                            .iter()
+                            .map(|name| RadioItem {
+                                label: name.clone(),
+                                ..Default::default()
+                            })
+                            .collect(),
+                    }
+                    .into()],
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        menu.push(MenuItem::Separator);
+        menu.push(
+            StandardItem {
+                label: "Quit".into(),
+                icon_name: "application-exit".into(),
+                activate: Box::new(|tray: &mut Self| {
+                    info!("Quit requested from tray");
+                    tray.running.store(false, Ordering::SeqCst);
+                }),
+                ..Default::default()
+            }
+            .into(),
+        );
+        menu
+    }
+}
+
+/// Start the tray icon and a background thread that keeps it current
+pub fn start_tray(
+    state: DaemonState,
+    running: Arc<AtomicBool>,
+) -> Result<thread::JoinHandle<()>, DoubleTapError> {
+    let tray = DoubleTapTray {
+        state: state.clone(),
+        running: running.clone(),
+    };
+    let handle = tray
+        .spawn()
+        .map_err(|e| DoubleTapError::Dbus(format!("Tray icon: {}", e)))?;
+    info!("Tray icon enabled");
+
+    Ok(thread::spawn(move || {
+        let mut last = Snapshot::from_state(&state);
+        while running.load(Ordering::SeqCst) && !handle.is_closed() {
+            thread::sleep(REFRESH_INTERVAL);
+            let current = Snapshot::from_state(&state);
+            if current != last {
+                // Tray properties read the live state; an empty update makes
+                // ksni re-query them and emit the change signals
+                handle.update(|_| {});
+                last = current;
+            }
+        }
+        handle.shutdown().wait();
+    }))
+}